@@ -0,0 +1,30 @@
+use actix_http::{HttpMessage, Payload, PayloadStream, Request};
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion};
+use futures::{stream, Future};
+
+// many tiny chunks, i.e. slow client with small tcp segments
+fn request() -> Request {
+    let chunks = vec![Bytes::from_static(b"01234567"); 16_384];
+    let stream: PayloadStream = Box::new(stream::iter_ok(chunks));
+    Request::with_payload(Payload::Stream(stream))
+}
+
+fn bench_message_body(c: &mut Criterion) {
+    c.bench_function("body", |b| {
+        b.iter(|| request().body().limit(1_048_576).wait().unwrap())
+    });
+    c.bench_function("body coalesce 4096", |b| {
+        b.iter(|| {
+            request()
+                .body()
+                .limit(1_048_576)
+                .coalesce(4096)
+                .wait()
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_message_body);
+criterion_main!(benches);
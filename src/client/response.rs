@@ -35,6 +35,10 @@ impl HttpMessage for ClientResponse {
     fn take_payload(&mut self) -> Payload {
         std::mem::replace(&mut self.payload, Payload::None)
     }
+
+    fn http_version(&self) -> Option<Version> {
+        Some(self.version())
+    }
 }
 
 impl ClientResponse {
@@ -8,12 +8,15 @@ use std::{fmt, io, result};
 // use actix::MailboxError;
 use actix_utils::timeout::TimeoutError;
 use backtrace::Backtrace;
+use bytes::Bytes;
 use cookie;
+use csv::Error as CsvParseError;
 use derive_more::{Display, From};
 use futures::Canceled;
 use http::uri::InvalidUri;
 use http::{header, Error as HttpError, StatusCode};
 use httparse;
+use rmp_serde::decode::Error as MsgPackError;
 use serde::de::value::Error as DeError;
 use serde_json::error::Error as JsonError;
 use serde_urlencoded::ser::Error as FormError;
@@ -235,6 +238,21 @@ pub enum ParseError {
     /// Parsing a field as string failed
     #[display(fmt = "UTF8 error: {}", _0)]
     Utf8(Utf8Error),
+    /// Required header is missing
+    #[display(fmt = "Required header is missing")]
+    MissingHeader,
+    /// Header value does not match expected value
+    #[display(fmt = "Header value does not match expected value")]
+    HeaderMismatch,
+    /// Header value contains bytes outside of printable ASCII
+    #[display(fmt = "Header {} contains non-ASCII value", _0)]
+    NonAsciiHeader(header::HeaderName),
+    /// Header value contains line folding artifacts
+    #[display(fmt = "Header {} contains folded value", _0)]
+    ObsFold(header::HeaderName),
+    /// Request target carries query string where none is allowed
+    #[display(fmt = "Unexpected query string")]
+    UnexpectedQuery,
 }
 
 /// Return `BadRequest` for `ParseError`
@@ -268,6 +286,16 @@ impl From<FromUtf8Error> for ParseError {
     }
 }
 
+impl From<PayloadError> for ParseError {
+    fn from(err: PayloadError) -> ParseError {
+        match err {
+            PayloadError::Overflow => ParseError::TooLarge,
+            PayloadError::Incomplete(None) => ParseError::Incomplete,
+            err => ParseError::Io(err.into()),
+        }
+    }
+}
+
 impl From<httparse::Error> for ParseError {
     fn from(err: httparse::Error) -> ParseError {
         match err {
@@ -297,6 +325,27 @@ pub enum PayloadError {
     /// A payload length is unknown.
     #[display(fmt = "A payload length is unknown.")]
     UnknownLength,
+    /// Content-Length is signed or has conflicting values.
+    #[display(fmt = "Invalid or conflicting content-length.")]
+    InvalidContentLength,
+    /// A payload checksum does not match expected value.
+    #[display(fmt = "A payload checksum does not match.")]
+    ChecksumMismatch,
+    /// A payload is received slower than allowed minimal rate.
+    #[display(fmt = "A payload is received too slow.")]
+    TooSlow,
+    /// No payload data received within inactivity timeout.
+    #[display(fmt = "A payload inactivity timeout.")]
+    Timeout,
+    /// A payload contains forbidden byte.
+    #[display(fmt = "A payload contains invalid byte.")]
+    InvalidByte,
+    /// A payload can not be decoded with declared charset.
+    #[display(fmt = "A payload can not be decoded with declared charset.")]
+    Charset,
+    /// A payload is not valid utf-8.
+    #[display(fmt = "A payload is not valid utf-8: {}", _0)]
+    Utf8(Utf8Error),
     /// Http2 payload error
     #[display(fmt = "{}", _0)]
     Http2Payload(h2::Error),
@@ -308,14 +357,31 @@ impl From<io::Error> for PayloadError {
     }
 }
 
+impl From<PayloadError> for io::Error {
+    fn from(err: PayloadError) -> Self {
+        let kind = match err {
+            PayloadError::Incomplete(Some(err)) => return err,
+            PayloadError::Incomplete(None) => io::ErrorKind::UnexpectedEof,
+            PayloadError::TooSlow | PayloadError::Timeout => io::ErrorKind::TimedOut,
+            PayloadError::Http2Payload(_) => io::ErrorKind::Other,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, format!("{}", err))
+    }
+}
+
 /// `PayloadError` returns two possible results:
 ///
 /// - `Overflow` returns `PayloadTooLarge`
+/// - `TooSlow` and `Timeout` return `RequestTimeout`
 /// - Other errors returns `BadRequest`
 impl ResponseError for PayloadError {
     fn error_response(&self) -> Response {
         match *self {
             PayloadError::Overflow => Response::new(StatusCode::PAYLOAD_TOO_LARGE),
+            PayloadError::TooSlow | PayloadError::Timeout => {
+                Response::new(StatusCode::REQUEST_TIMEOUT)
+            }
             _ => Response::new(StatusCode::BAD_REQUEST),
         }
     }
@@ -328,6 +394,24 @@ impl ResponseError for cookie::ParseError {
     }
 }
 
+/// A set of errors that can occur during loading request cookies
+#[derive(Debug, Display, From)]
+pub enum CookieError {
+    /// Cookie parse error
+    #[display(fmt = "{}", _0)]
+    Parse(CookieParseError),
+    /// Request contains more cookies than allowed
+    #[display(fmt = "Too many cookies")]
+    TooMany,
+}
+
+/// Return `BadRequest` for `CookieError`
+impl ResponseError for CookieError {
+    fn error_response(&self) -> Response {
+        Response::new(StatusCode::BAD_REQUEST)
+    }
+}
+
 #[derive(Debug, Display, From)]
 /// A set of errors that can occur during dispatching http requests
 pub enum DispatchError {
@@ -381,6 +465,9 @@ pub enum ContentTypeError {
     /// Unknown content encoding
     #[display(fmt = "Unknown content encoding")]
     UnknownEncoding,
+    /// Content type header is missing
+    #[display(fmt = "Content type is missing")]
+    Missing,
 }
 
 /// Return `BadRequest` for `ContentTypeError`
@@ -391,7 +478,7 @@ impl ResponseError for ContentTypeError {
 }
 
 /// A set of errors that can occur during parsing urlencoded payloads
-#[derive(Debug, Display, From)]
+#[derive(Debug, Display)]
 pub enum UrlencodedError {
     /// Can not decode chunked transfer encoding
     #[display(fmt = "Can not decode chunked transfer encoding")]
@@ -399,6 +486,13 @@ pub enum UrlencodedError {
     /// Payload size is bigger than allowed. (default: 256kB)
     #[display(fmt = "Urlencoded payload size is bigger than allowed. (default: 256kB)")]
     Overflow,
+    /// Payload size is bigger than allowed, carries body prefix collected
+    /// before the limit was hit.
+    #[display(fmt = "Urlencoded payload size is bigger than allowed. (default: 256kB)")]
+    OverflowWithData(Bytes),
+    /// Single field size is bigger than allowed.
+    #[display(fmt = "Urlencoded field size is bigger than allowed")]
+    FieldOverflow,
     /// Payload size is now known
     #[display(fmt = "Payload size is now known")]
     UnknownLength,
@@ -413,11 +507,21 @@ pub enum UrlencodedError {
     Payload(PayloadError),
 }
 
+impl From<PayloadError> for UrlencodedError {
+    fn from(err: PayloadError) -> Self {
+        UrlencodedError::Payload(err)
+    }
+}
+
 /// Return `BadRequest` for `UrlencodedError`
 impl ResponseError for UrlencodedError {
     fn error_response(&self) -> Response {
         match *self {
-            UrlencodedError::Overflow => Response::new(StatusCode::PAYLOAD_TOO_LARGE),
+            UrlencodedError::Overflow
+            | UrlencodedError::OverflowWithData(_)
+            | UrlencodedError::FieldOverflow => {
+                Response::new(StatusCode::PAYLOAD_TOO_LARGE)
+            }
             UrlencodedError::UnknownLength => Response::new(StatusCode::LENGTH_REQUIRED),
             _ => Response::new(StatusCode::BAD_REQUEST),
         }
@@ -436,6 +540,9 @@ pub enum JsonPayloadError {
     /// Deserialize error
     #[display(fmt = "Json deserialize error: {}", _0)]
     Deserialize(JsonError),
+    /// Json stream structure is malformed
+    #[display(fmt = "Json stream is malformed")]
+    Malformed,
     /// Payload error
     #[display(fmt = "Error that occur during reading payload: {}", _0)]
     Payload(PayloadError),
@@ -451,6 +558,60 @@ impl ResponseError for JsonPayloadError {
     }
 }
 
+/// A set of errors that can occur during parsing csv payloads
+#[derive(Debug, Display, From)]
+pub enum CsvError {
+    /// Record or payload size is bigger than allowed
+    #[display(fmt = "Csv payload size is bigger than allowed")]
+    Overflow,
+    /// Deserialize error
+    #[display(fmt = "Csv deserialize error: {}", _0)]
+    Deserialize(CsvParseError),
+    /// Payload error
+    #[display(fmt = "Error that occur during reading payload: {}", _0)]
+    Payload(PayloadError),
+}
+
+/// Return `BadRequest` for `CsvError`
+impl ResponseError for CsvError {
+    fn error_response(&self) -> Response {
+        match *self {
+            CsvError::Overflow => Response::new(StatusCode::PAYLOAD_TOO_LARGE),
+            _ => Response::new(StatusCode::BAD_REQUEST),
+        }
+    }
+}
+
+/// A set of errors that can occur during parsing sniffed json or msgpack
+/// payloads
+#[derive(Debug, Display, From)]
+pub enum SniffError {
+    /// Payload format can not be detected
+    #[display(fmt = "Can not detect payload format")]
+    UnknownFormat,
+    /// Json deserialize error
+    #[display(fmt = "Json deserialize error: {}", _0)]
+    Json(JsonError),
+    /// Msgpack deserialize error
+    #[display(fmt = "Msgpack deserialize error: {}", _0)]
+    MsgPack(MsgPackError),
+    /// Payload error
+    #[display(fmt = "Error that occur during reading payload: {}", _0)]
+    Payload(PayloadError),
+}
+
+/// Return `BadRequest` for `SniffError`
+impl ResponseError for SniffError {
+    fn error_response(&self) -> Response {
+        match *self {
+            SniffError::Payload(PayloadError::Overflow) => {
+                Response::new(StatusCode::PAYLOAD_TOO_LARGE)
+            }
+            _ => Response::new(StatusCode::BAD_REQUEST),
+        }
+    }
+}
+
 /// Error type returned when reading body as lines.
 #[derive(From)]
 pub enum ReadlinesError {
@@ -460,10 +621,30 @@ pub enum ReadlinesError {
     PayloadError(PayloadError),
     /// Line limit exceeded.
     LimitOverflow,
+    /// Payload ended without line terminator.
+    Incomplete,
     /// ContentType error.
     ContentTypeError(ContentTypeError),
 }
 
+/// Error type returned when forwarding payload to a sink.
+#[derive(Debug)]
+pub enum ForwardError<E> {
+    /// Payload error.
+    Payload(PayloadError),
+    /// Sink error.
+    Sink(E),
+}
+
+impl<E: fmt::Display> fmt::Display for ForwardError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ForwardError::Payload(e) => write!(f, "Payload error: {}", e),
+            ForwardError::Sink(e) => write!(f, "Sink error: {}", e),
+        }
+    }
+}
+
 /// Helper type that can wrap any error and generate custom response.
 ///
 /// In following example any `io::Error` will be converted into "BAD REQUEST"
@@ -15,6 +15,8 @@ pub use self::dispatcher::Dispatcher;
 pub use self::payload::{Payload, PayloadBuffer};
 pub use self::service::{H1Service, H1ServiceHandler, OneRequest};
 
+pub(crate) use self::decoder::{PayloadDecoder, PayloadItem};
+
 #[derive(Debug)]
 /// Codec message
 pub enum Message<T> {
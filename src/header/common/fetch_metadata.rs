@@ -0,0 +1,196 @@
+use crate::header::HeaderMap;
+
+/// Fetch Metadata request headers, defined in
+/// [Fetch Metadata](https://w3c.github.io/webappsec-fetch-metadata/)
+///
+/// Browsers send `Sec-Fetch-Site`, `Sec-Fetch-Mode`, `Sec-Fetch-Dest` and
+/// `Sec-Fetch-User` headers to describe the context of a request. Field is
+/// `None` if corresponding header is absent or is not valid utf-8. Unknown
+/// values are preserved for forward compatibility.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FetchMetadata {
+    /// `Sec-Fetch-Site` header
+    pub site: Option<FetchSite>,
+    /// `Sec-Fetch-Mode` header
+    pub mode: Option<FetchMode>,
+    /// `Sec-Fetch-Dest` header
+    pub dest: Option<FetchDest>,
+    /// `Sec-Fetch-User` header, `true` for user activated navigation
+    pub user: Option<bool>,
+}
+
+impl FetchMetadata {
+    /// Parse Fetch Metadata headers.
+    pub fn from_headers(headers: &HeaderMap) -> FetchMetadata {
+        let get = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+        FetchMetadata {
+            site: get("sec-fetch-site").map(FetchSite::from),
+            mode: get("sec-fetch-mode").map(FetchMode::from),
+            dest: get("sec-fetch-dest").map(FetchDest::from),
+            user: get("sec-fetch-user").map(|s| s.trim() == "?1"),
+        }
+    }
+}
+
+/// Value of `Sec-Fetch-Site` header
+#[derive(Clone, Debug, PartialEq)]
+pub enum FetchSite {
+    /// `cross-site`
+    CrossSite,
+    /// `same-origin`
+    SameOrigin,
+    /// `same-site`
+    SameSite,
+    /// `none`, request is initiated by the user
+    None,
+    /// Some other value
+    Unknown(String),
+}
+
+impl<'a> From<&'a str> for FetchSite {
+    fn from(s: &'a str) -> FetchSite {
+        let s = s.trim();
+        match s.to_ascii_lowercase().as_str() {
+            "cross-site" => FetchSite::CrossSite,
+            "same-origin" => FetchSite::SameOrigin,
+            "same-site" => FetchSite::SameSite,
+            "none" => FetchSite::None,
+            _ => FetchSite::Unknown(s.to_owned()),
+        }
+    }
+}
+
+/// Value of `Sec-Fetch-Mode` header
+#[derive(Clone, Debug, PartialEq)]
+pub enum FetchMode {
+    /// `cors`
+    Cors,
+    /// `navigate`
+    Navigate,
+    /// `no-cors`
+    NoCors,
+    /// `same-origin`
+    SameOrigin,
+    /// `websocket`
+    Websocket,
+    /// Some other value
+    Unknown(String),
+}
+
+impl<'a> From<&'a str> for FetchMode {
+    fn from(s: &'a str) -> FetchMode {
+        let s = s.trim();
+        match s.to_ascii_lowercase().as_str() {
+            "cors" => FetchMode::Cors,
+            "navigate" => FetchMode::Navigate,
+            "no-cors" => FetchMode::NoCors,
+            "same-origin" => FetchMode::SameOrigin,
+            "websocket" => FetchMode::Websocket,
+            _ => FetchMode::Unknown(s.to_owned()),
+        }
+    }
+}
+
+/// Value of `Sec-Fetch-Dest` header
+#[derive(Clone, Debug, PartialEq)]
+pub enum FetchDest {
+    /// `audio`
+    Audio,
+    /// `audioworklet`
+    AudioWorklet,
+    /// `document`
+    Document,
+    /// `embed`
+    Embed,
+    /// `empty`
+    Empty,
+    /// `font`
+    Font,
+    /// `frame`
+    Frame,
+    /// `iframe`
+    Iframe,
+    /// `image`
+    Image,
+    /// `manifest`
+    Manifest,
+    /// `object`
+    Object,
+    /// `paintworklet`
+    PaintWorklet,
+    /// `report`
+    Report,
+    /// `script`
+    Script,
+    /// `serviceworker`
+    ServiceWorker,
+    /// `sharedworker`
+    SharedWorker,
+    /// `style`
+    Style,
+    /// `track`
+    Track,
+    /// `video`
+    Video,
+    /// `worker`
+    Worker,
+    /// `xslt`
+    Xslt,
+    /// Some other value
+    Unknown(String),
+}
+
+impl<'a> From<&'a str> for FetchDest {
+    fn from(s: &'a str) -> FetchDest {
+        let s = s.trim();
+        match s.to_ascii_lowercase().as_str() {
+            "audio" => FetchDest::Audio,
+            "audioworklet" => FetchDest::AudioWorklet,
+            "document" => FetchDest::Document,
+            "embed" => FetchDest::Embed,
+            "empty" => FetchDest::Empty,
+            "font" => FetchDest::Font,
+            "frame" => FetchDest::Frame,
+            "iframe" => FetchDest::Iframe,
+            "image" => FetchDest::Image,
+            "manifest" => FetchDest::Manifest,
+            "object" => FetchDest::Object,
+            "paintworklet" => FetchDest::PaintWorklet,
+            "report" => FetchDest::Report,
+            "script" => FetchDest::Script,
+            "serviceworker" => FetchDest::ServiceWorker,
+            "sharedworker" => FetchDest::SharedWorker,
+            "style" => FetchDest::Style,
+            "track" => FetchDest::Track,
+            "video" => FetchDest::Video,
+            "worker" => FetchDest::Worker,
+            "xslt" => FetchDest::Xslt,
+            _ => FetchDest::Unknown(s.to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::httpmessage::HttpMessage;
+    use crate::test::TestRequest;
+
+    #[test]
+    fn test_fetch_metadata() {
+        let req = TestRequest::with_header("sec-fetch-site", "same-origin")
+            .header("sec-fetch-mode", "navigate")
+            .header("sec-fetch-dest", "x-future")
+            .header("sec-fetch-user", "?1")
+            .finish();
+        let meta = req.fetch_metadata();
+        assert_eq!(meta.site, Some(FetchSite::SameOrigin));
+        assert_eq!(meta.mode, Some(FetchMode::Navigate));
+        assert_eq!(meta.dest, Some(FetchDest::Unknown("x-future".to_owned())));
+        assert_eq!(meta.user, Some(true));
+
+        let req = TestRequest::default().finish();
+        assert_eq!(req.fetch_metadata(), FetchMetadata::default());
+    }
+}
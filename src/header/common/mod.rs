@@ -8,7 +8,7 @@
 #![cfg_attr(rustfmt, rustfmt_skip)]
 
 pub use self::accept_charset::AcceptCharset;
-//pub use self::accept_encoding::AcceptEncoding;
+pub use self::accept_encoding::AcceptEncoding;
 pub use self::accept_language::AcceptLanguage;
 pub use self::accept::Accept;
 pub use self::allow::Allow;
@@ -20,13 +20,16 @@ pub use self::content_type::ContentType;
 pub use self::date::Date;
 pub use self::etag::ETag;
 pub use self::expires::Expires;
+pub use self::fetch_metadata::{FetchDest, FetchMetadata, FetchMode, FetchSite};
 pub use self::if_match::IfMatch;
 pub use self::if_modified_since::IfModifiedSince;
 pub use self::if_none_match::IfNoneMatch;
 pub use self::if_range::IfRange;
 pub use self::if_unmodified_since::IfUnmodifiedSince;
 pub use self::last_modified::LastModified;
-//pub use self::range::{Range, ByteRangeSpec};
+pub use self::range::{ByteRangeSpec, Range};
+pub use self::sigv4::{SigV4, SigV4Scope};
+pub use self::warning::WarningHeader;
 
 #[doc(hidden)]
 #[macro_export]
@@ -332,7 +335,7 @@ macro_rules! header {
 
 
 mod accept_charset;
-//mod accept_encoding;
+mod accept_encoding;
 mod accept_language;
 mod accept;
 mod allow;
@@ -344,9 +347,13 @@ mod content_type;
 mod date;
 mod etag;
 mod expires;
+mod fetch_metadata;
 mod if_match;
 mod if_modified_since;
 mod if_none_match;
 mod if_range;
 mod if_unmodified_since;
 mod last_modified;
+mod range;
+mod sigv4;
+mod warning;
@@ -0,0 +1,141 @@
+use std::str::FromStr;
+
+use crate::error::ParseError;
+use crate::header::{HeaderMap, AUTHORIZATION};
+
+/// AWS Signature Version 4 *Authorization* header, defined in
+/// [AWS docs](https://docs.aws.amazon.com/general/latest/gr/sigv4-add-signature-to-request.html)
+///
+/// # Example values
+///
+/// * `AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request,
+///   SignedHeaders=content-type;host;x-amz-date, Signature=5d672d79...`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SigV4 {
+    /// Access key id
+    pub access_key: String,
+    /// Credential scope
+    pub scope: SigV4Scope,
+    /// Lowercase names of signed headers
+    pub signed_headers: Vec<String>,
+    /// Hex encoded signature
+    pub signature: String,
+}
+
+/// Credential scope of `SigV4` authorization
+#[derive(Clone, Debug, PartialEq)]
+pub struct SigV4Scope {
+    /// Date in `YYYYMMDD` format
+    pub date: String,
+    /// Region, i.e. `us-east-1`
+    pub region: String,
+    /// Service, i.e. `s3`
+    pub service: String,
+}
+
+impl SigV4 {
+    /// Parse *Authorization* header.
+    ///
+    /// Returns `None` if header is absent or does not use `AWS4-HMAC-SHA256`
+    /// scheme.
+    pub fn from_headers(headers: &HeaderMap) -> Option<SigV4> {
+        headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| s.parse().ok())
+    }
+}
+
+impl FromStr for SigV4 {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<SigV4, ParseError> {
+        let s = s.trim();
+        if !s.starts_with("AWS4-HMAC-SHA256 ") {
+            return Err(ParseError::Header);
+        }
+
+        let mut credential = None;
+        let mut signed_headers = None;
+        let mut signature = None;
+        for param in s["AWS4-HMAC-SHA256 ".len()..].split(',') {
+            let mut parts = param.trim().splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("Credential"), Some(val)) => credential = Some(val),
+                (Some("SignedHeaders"), Some(val)) => signed_headers = Some(val),
+                (Some("Signature"), Some(val)) => signature = Some(val),
+                _ => return Err(ParseError::Header),
+            }
+        }
+
+        let credential = credential.ok_or(ParseError::Header)?;
+        let parts: Vec<_> = credential.split('/').collect();
+        if parts.len() != 5 || parts[4] != "aws4_request" {
+            return Err(ParseError::Header);
+        }
+        let signed_headers = signed_headers.ok_or(ParseError::Header)?;
+        let signature = signature.ok_or(ParseError::Header)?;
+        if parts.iter().any(|p| p.is_empty())
+            || signed_headers.is_empty()
+            || signature.is_empty()
+        {
+            return Err(ParseError::Header);
+        }
+
+        Ok(SigV4 {
+            access_key: parts[0].to_owned(),
+            scope: SigV4Scope {
+                date: parts[1].to_owned(),
+                region: parts[2].to_owned(),
+                service: parts[3].to_owned(),
+            },
+            signed_headers: signed_headers.split(';').map(|h| h.to_owned()).collect(),
+            signature: signature.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::httpmessage::HttpMessage;
+    use crate::test::TestRequest;
+
+    #[test]
+    fn test_sigv4() {
+        let req = TestRequest::with_header(
+            AUTHORIZATION,
+            "AWS4-HMAC-SHA256 \
+             Credential=AKIDEXAMPLE/20150830/us-east-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-date, Signature=5d672d79c15b13162d9279b0855cfba6",
+        )
+        .finish();
+        assert_eq!(
+            req.aws_sigv4(),
+            Some(SigV4 {
+                access_key: "AKIDEXAMPLE".to_owned(),
+                scope: SigV4Scope {
+                    date: "20150830".to_owned(),
+                    region: "us-east-1".to_owned(),
+                    service: "s3".to_owned(),
+                },
+                signed_headers: vec!["host".to_owned(), "x-amz-date".to_owned()],
+                signature: "5d672d79c15b13162d9279b0855cfba6".to_owned(),
+            })
+        );
+
+        let req = TestRequest::with_header(AUTHORIZATION, "Basic dXNlcjpwYXNz").finish();
+        assert_eq!(req.aws_sigv4(), None);
+
+        let req = TestRequest::with_header(
+            AUTHORIZATION,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/s3, \
+             SignedHeaders=host, Signature=5d67",
+        )
+        .finish();
+        assert_eq!(req.aws_sigv4(), None);
+
+        let req = TestRequest::default().finish();
+        assert_eq!(req.aws_sigv4(), None);
+    }
+}
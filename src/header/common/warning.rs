@@ -0,0 +1,160 @@
+use std::str::FromStr;
+
+use crate::error::ParseError;
+use crate::header::{HeaderMap, HttpDate, WARNING};
+
+/// Single entry of `Warning` header, defined in
+/// [RFC7234](https://tools.ietf.org/html/rfc7234#section-5.5)
+///
+/// # ABNF
+///
+/// ```text
+/// Warning       = 1#warning-value
+/// warning-value = warn-code SP warn-agent SP warn-text [ SP warn-date ]
+/// ```
+///
+/// # Example values
+/// * `110 anderson/1.3.37 "Response is stale"`
+/// * `112 - "network down" "Sat, 25 Aug 2012 23:34:45 GMT"`
+#[derive(Clone, Debug, PartialEq)]
+pub struct WarningHeader {
+    /// Three digit warning code
+    pub code: u16,
+    /// Host name or pseudonym of the agent adding the warning
+    pub agent: String,
+    /// Warning text, unquoted
+    pub text: String,
+    /// Optional date of the warning
+    pub date: Option<HttpDate>,
+}
+
+impl WarningHeader {
+    /// Parse all `Warning` headers. Malformed entries are skipped.
+    pub fn from_headers(headers: &HeaderMap) -> Vec<WarningHeader> {
+        let mut warnings = Vec::new();
+        for hdr in headers.get_all(WARNING) {
+            if let Ok(mut rest) = hdr.to_str() {
+                loop {
+                    rest =
+                        rest.trim_start_matches(|c| c == ',' || c == ' ' || c == '\t');
+                    if rest.is_empty() {
+                        break;
+                    }
+                    let end = entry_end(rest);
+                    if let Ok(warning) = rest[..end].parse() {
+                        warnings.push(warning);
+                    }
+                    rest = &rest[end..];
+                }
+            }
+        }
+        warnings
+    }
+}
+
+impl FromStr for WarningHeader {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<WarningHeader, ParseError> {
+        let s = s.trim();
+        let mut parts = s.splitn(3, ' ');
+        let code = parts.next().ok_or(ParseError::Header)?;
+        if code.len() != 3 || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseError::Header);
+        }
+        let agent = parts.next().ok_or(ParseError::Header)?;
+        let rest = parts.next().ok_or(ParseError::Header)?;
+
+        let (text, rest) = quoted(rest.trim_start()).ok_or(ParseError::Header)?;
+        let rest = rest.trim();
+        let date = if rest.is_empty() {
+            None
+        } else {
+            match quoted(rest) {
+                Some((date, tail)) if tail.trim().is_empty() => Some(date.parse()?),
+                _ => return Err(ParseError::Header),
+            }
+        };
+
+        Ok(WarningHeader {
+            code: code.parse().map_err(|_| ParseError::Header)?,
+            agent: agent.to_owned(),
+            text,
+            date,
+        })
+    }
+}
+
+/// Position of the comma separating warning entries, quoted strings
+/// may contain commas.
+fn entry_end(s: &str) -> usize {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (idx, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == ',' && !in_quotes {
+            return idx;
+        }
+    }
+    s.len()
+}
+
+/// Parse leading quoted string, returns unescaped value and the rest.
+fn quoted(s: &str) -> Option<(String, &str)> {
+    if !s.starts_with('"') {
+        return None;
+    }
+    let mut value = String::new();
+    let mut escaped = false;
+    for (idx, c) in s.char_indices().skip(1) {
+        if escaped {
+            value.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            return Some((value, &s[idx + 1..]));
+        } else {
+            value.push(c);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::httpmessage::HttpMessage;
+    use crate::test::TestRequest;
+
+    #[test]
+    fn test_warnings() {
+        let req = TestRequest::with_header(
+            WARNING,
+            "110 anderson/1.3.37 \"Response, \\\"is\\\" stale\", garbage, \
+             112 - \"network down\" \"Sat, 25 Aug 2012 23:34:45 GMT\"",
+        )
+        .header(WARNING, "299 proxy.example \"misc\"")
+        .finish();
+
+        let warnings = req.warnings();
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(warnings[0].code, 110);
+        assert_eq!(warnings[0].agent, "anderson/1.3.37");
+        assert_eq!(warnings[0].text, "Response, \"is\" stale");
+        assert_eq!(warnings[0].date, None);
+        assert_eq!(warnings[1].code, 112);
+        assert_eq!(warnings[1].agent, "-");
+        assert!(warnings[1].date.is_some());
+        assert_eq!(warnings[2].code, 299);
+        assert_eq!(warnings[2].text, "misc");
+
+        let req = TestRequest::default().finish();
+        assert!(req.warnings().is_empty());
+    }
+}
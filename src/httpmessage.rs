@@ -1,28 +1,460 @@
 use std::cell::{Ref, RefMut};
+use std::io;
+use std::marker::PhantomData;
 use std::str;
+use std::time::{Duration, Instant, SystemTime};
 
+use actix_codec::AsyncRead;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use bytes::{Bytes, BytesMut};
 use cookie::Cookie;
+use csv::ByteRecord;
+use csv_core::{ReadRecordResult, Reader as CsvReader};
 use encoding::all::UTF_8;
 use encoding::label::encoding_from_whatwg_label;
 use encoding::types::{DecoderTrap, Encoding};
 use encoding::EncodingRef;
-use futures::{Async, Future, Poll, Stream};
-use http::{header, HeaderMap};
+use futures::future::{err, ok, FromErr, MapErr};
+use futures::{Async, Future, Poll, Sink, Stream};
+use hmac::{Hmac, Mac};
+use http::header::{AsHeaderName, HeaderName, HeaderValue};
+use http::{header, HeaderMap, Version};
 use mime::Mime;
-use serde::de::DeserializeOwned;
+use serde::de::{DeserializeOwned, IgnoredAny};
 use serde_urlencoded;
+use sha2::{Digest, Sha256, Sha512};
+use url::Url;
 
 use crate::error::{
-    ContentTypeError, CookieParseError, ParseError, PayloadError, ReadlinesError,
+    ContentTypeError, CookieError, CookieParseError, CsvError, ForwardError,
+    JsonPayloadError, ParseError, PayloadError, ReadlinesError, SniffError,
     UrlencodedError,
 };
 use crate::extensions::Extensions;
-use crate::header::Header;
-use crate::json::JsonBody;
-use crate::payload::Payload;
+use crate::header::{
+    q, Accept, AcceptCharset, AcceptEncoding, ContentEncoding, Encoding, EntityTag,
+    FetchMetadata, Header, HttpDate, IfRange, Quality, QualityItem, Range, SigV4,
+    WarningHeader,
+};
+use crate::json::{CspReport, JsonArrayStream, JsonBody};
+use crate::payload::{
+    content_encoding, Deadline, Decoder, InactivityTimeout, MinRate, OnStall, Payload,
+    PayloadStream,
+};
+
+/// Default max number of cookies parsed by `HttpMessage::cookies()`
+pub const MAX_COOKIES: usize = 512;
+
+/// *Idempotency-Key* header name
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Maximum number of byte ranges honored by `HttpMessage::resolve_range()`
+const MAX_RANGES: usize = 100;
+
+/// Cached request cookies
+struct Cookies {
+    cookies: Vec<Cookie<'static>>,
+    // max number of cookies parsed
+    limit: usize,
+    // request contains more than `limit` cookies
+    too_many: bool,
+}
+
+/// Hash algorithm, see `HttpMessage::body_expect_hash()`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HashAlgo {
+    /// SHA-256
+    Sha256,
+    /// SHA-512
+    Sha512,
+}
+
+impl HashAlgo {
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgo::Sha256 => Sha256::digest(data).to_vec(),
+            HashAlgo::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            HashAlgo::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgo::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+}
+
+/// Incremental hasher for `HashAlgo`
+enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn input(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.input(data),
+            Hasher::Sha512(h) => h.input(data),
+        }
+    }
+
+    fn result(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha256(h) => h.result().to_vec(),
+            Hasher::Sha512(h) => h.result().to_vec(),
+        }
+    }
+}
+
+/// Parse header block without terminating empty line
+fn parse_header_block(head: &[u8]) -> Result<HeaderMap, ParseError> {
+    let mut buf = BytesMut::with_capacity(head.len() + 4);
+    buf.extend_from_slice(head);
+    buf.extend_from_slice(b"\r\n\r\n");
+
+    let mut parsed = [httparse::EMPTY_HEADER; 96];
+    match httparse::parse_headers(&buf, &mut parsed)? {
+        httparse::Status::Complete((_, parsed)) => {
+            let mut headers = HeaderMap::with_capacity(parsed.len());
+            for hdr in parsed {
+                let name = HeaderName::from_bytes(hdr.name.as_bytes())
+                    .map_err(|_| ParseError::Header)?;
+                let value = HeaderValue::from_bytes(hdr.value)
+                    .map_err(|_| ParseError::Header)?;
+                headers.append(name, value);
+            }
+            Ok(headers)
+        }
+        httparse::Status::Partial => Err(ParseError::Incomplete),
+    }
+}
+
+/// HMAC algorithm, see `HttpMessage::verify_hmac()`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HmacAlgo {
+    /// HMAC-SHA256
+    Sha256,
+    /// HMAC-SHA512
+    Sha512,
+}
+
+impl HmacAlgo {
+    fn mac(self, key: &[u8]) -> HmacState {
+        // hmac accepts keys of any length
+        match self {
+            HmacAlgo::Sha256 => {
+                HmacState::Sha256(Hmac::new_varkey(key).expect("Invalid key length"))
+            }
+            HmacAlgo::Sha512 => {
+                HmacState::Sha512(Hmac::new_varkey(key).expect("Invalid key length"))
+            }
+        }
+    }
+}
+
+/// Incremental HMAC for `HmacAlgo`
+enum HmacState {
+    Sha256(Hmac<Sha256>),
+    Sha512(Hmac<Sha512>),
+}
+
+impl HmacState {
+    fn input(&mut self, data: &[u8]) {
+        match self {
+            HmacState::Sha256(mac) => mac.input(data),
+            HmacState::Sha512(mac) => mac.input(data),
+        }
+    }
+
+    /// Constant time comparison with `code`
+    fn verify(self, code: &[u8]) -> bool {
+        match self {
+            HmacState::Sha256(mac) => mac.verify(code).is_ok(),
+            HmacState::Sha512(mac) => mac.verify(code).is_ok(),
+        }
+    }
+}
+
+/// Parse hex encoded signature, optionally prefixed with `algo=`
+fn parse_signature(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim();
+    let hex = match value.find('=') {
+        Some(idx) => &value[idx + 1..],
+        None => value,
+    };
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect()
+}
+
+/// Find first *Digest* header entry with supported algorithm
+fn parse_digest(value: &str) -> Option<(HashAlgo, Vec<u8>)> {
+    for entry in value.split(',') {
+        let mut parts = entry.trim().splitn(2, '=');
+        let algo = match parts.next() {
+            Some(algo) if algo.eq_ignore_ascii_case("sha-256") => HashAlgo::Sha256,
+            Some(algo) if algo.eq_ignore_ascii_case("sha-512") => HashAlgo::Sha512,
+            _ => continue,
+        };
+        return parts
+            .next()
+            .and_then(|digest| base64::decode(digest).ok())
+            .map(|digest| (algo, digest));
+    }
+    None
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BodyFormat {
+    Json,
+    MsgPack,
+}
+
+/// Detect body format by its first significant byte,
+/// `content_type` is used only if the body is inconclusive.
+fn sniff_format(body: &[u8], content_type: &str) -> Option<BodyFormat> {
+    match body.iter().cloned().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') | Some(b'[') => return Some(BodyFormat::Json),
+        Some(0x80..=0x9f) | Some(0xdc..=0xdf) => return Some(BodyFormat::MsgPack),
+        _ => (),
+    }
+    let content_type = content_type.to_lowercase();
+    if content_type == "application/json" || content_type.ends_with("+json") {
+        Some(BodyFormat::Json)
+    } else if content_type.ends_with("msgpack") {
+        Some(BodyFormat::MsgPack)
+    } else {
+        None
+    }
+}
+
+/// Max length of transport padding before quoted-printable soft line break,
+/// encoded lines are limited to 76 characters (RFC 2045)
+const QP_MAX_PADDING: usize = 76;
 
-struct Cookies(Vec<Cookie<'static>>);
+/// Decode quoted-printable `input` into `out`.
+///
+/// Returns number of consumed bytes, incomplete escape sequence at the end
+/// of `input` is left unconsumed unless `eof` is set.
+fn decode_quoted_printable(
+    input: &[u8],
+    out: &mut BytesMut,
+    eof: bool,
+) -> Result<usize, PayloadError> {
+    let mut pos = 0;
+    while pos < input.len() {
+        let run = input[pos..]
+            .iter()
+            .position(|b| *b == b'=')
+            .unwrap_or(input.len() - pos);
+        out.extend_from_slice(&input[pos..pos + run]);
+        pos += run;
+        if pos == input.len() {
+            break;
+        }
+
+        // soft line break, transport padding is allowed before it
+        let rest = &input[pos + 1..];
+        let pad = rest
+            .iter()
+            .take_while(|b| **b == b' ' || **b == b'\t')
+            .count();
+        if pad > QP_MAX_PADDING {
+            return Err(PayloadError::EncodingCorrupted);
+        }
+        let seq = &rest[pad..];
+        if seq.starts_with(b"\r\n") {
+            pos += pad + 3;
+        } else if seq.starts_with(b"\n") {
+            pos += pad + 2;
+        } else if seq.is_empty() || seq == b"\r" || (pad == 0 && seq.len() == 1) {
+            if !eof {
+                return Ok(pos);
+            }
+            if !seq.is_empty() {
+                return Err(PayloadError::EncodingCorrupted);
+            }
+            // trailing soft line break
+            pos += pad + 1;
+        } else if pad == 0 {
+            let hi = (seq[0] as char).to_digit(16);
+            let lo = (seq[1] as char).to_digit(16);
+            match (hi, lo) {
+                (Some(hi), Some(lo)) => out.extend_from_slice(&[(hi * 16 + lo) as u8]),
+                _ => return Err(PayloadError::EncodingCorrupted),
+            }
+            pos += 3;
+        } else {
+            return Err(PayloadError::EncodingCorrupted);
+        }
+    }
+    Ok(pos)
+}
+
+/// Find `delimiter` in `buf` that is filled chunk by chunk.
+///
+/// Search starts at `checked` offset, on miss the offset is advanced, so
+/// already scanned bytes are not scanned again and delimiter may straddle
+/// chunk boundaries. Returns position of the delimiter, more than `limit`
+/// bytes before it cause `PayloadError::Overflow` error.
+fn find_delimiter(
+    buf: &[u8],
+    delimiter: &[u8],
+    checked: &mut usize,
+    limit: usize,
+) -> Result<Option<usize>, PayloadError> {
+    let dlen = delimiter.len();
+    if buf.len() >= dlen {
+        let found = buf[*checked..].windows(dlen).position(|w| w == delimiter);
+        if let Some(pos) = found {
+            let end = *checked + pos;
+            if end > limit {
+                return Err(PayloadError::Overflow);
+            }
+            *checked = 0;
+            return Ok(Some(end));
+        }
+        // delimiter may continue in the next chunk
+        *checked = buf.len() - dlen + 1;
+    }
+    if buf.len() > limit.saturating_add(dlen - 1) {
+        return Err(PayloadError::Overflow);
+    }
+    Ok(None)
+}
+
+/// Parse *Content-Length* header(s).
+///
+/// Surrounding whitespace is ignored. Repeated values, whether in separate
+/// headers or comma separated, must be identical.
+pub(crate) fn parse_content_length(
+    headers: &HeaderMap,
+) -> Result<Option<usize>, PayloadError> {
+    let mut len = None;
+    for hdr in headers.get_all(header::CONTENT_LENGTH) {
+        let s = hdr.to_str().map_err(|_| PayloadError::UnknownLength)?;
+        for val in s.split(',') {
+            let val = val.trim();
+            if val.starts_with('+') || val.starts_with('-') {
+                return Err(PayloadError::InvalidContentLength);
+            }
+            let val = val
+                .parse::<usize>()
+                .map_err(|_| PayloadError::UnknownLength)?;
+            match len {
+                Some(len) if len != val => {
+                    return Err(PayloadError::InvalidContentLength)
+                }
+                _ => len = Some(val),
+            }
+        }
+    }
+    Ok(len)
+}
+
+/// Parse at most `max` request cookies and cache them in message extensions.
+fn parse_cookies<T: HttpMessage>(msg: &T, max: usize) -> Result<(), CookieParseError> {
+    let mut cookies = Vec::new();
+    let mut too_many = false;
+    'headers: for hdr in msg.headers().get_all(header::COOKIE) {
+        let s = str::from_utf8(hdr.as_bytes())?;
+        for cookie_str in s.split(';').map(|s| s.trim()) {
+            if !cookie_str.is_empty() {
+                if cookies.len() == max {
+                    too_many = true;
+                    break 'headers;
+                }
+                cookies.push(Cookie::parse_encoded(cookie_str)?.into_owned());
+            }
+        }
+    }
+    msg.extensions_mut().insert(Cookies {
+        cookies,
+        limit: max,
+        too_many,
+    });
+    Ok(())
+}
+
+/// Fold message payload with `f`.
+///
+/// Fails with `PayloadError::Overflow` if *Content-Length* or size of the
+/// received payload exceeds `limit`.
+fn fold_payload<T, S, F>(
+    msg: &mut T,
+    limit: usize,
+    init: S,
+    f: F,
+) -> Box<Future<Item = S, Error = PayloadError>>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    S: 'static,
+    F: FnMut(S, Bytes) -> Result<S, PayloadError> + 'static,
+{
+    match parse_content_length(msg.headers()) {
+        Ok(Some(len)) if len > limit => return Box::new(err(PayloadError::Overflow)),
+        Err(e) => return Box::new(err(e)),
+        _ => (),
+    }
+    Box::new(msg.take_payload().limited(limit).fold(init, f))
+}
+
+/// Policy for resolving cookies with duplicate names,
+/// see `HttpMessage::cookies_dedup()`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DedupPolicy {
+    /// Keep the first cookie with the name
+    FirstWins,
+    /// Keep the last cookie with the name
+    LastWins,
+}
+
+/// Statistics collected while reading message body,
+/// see `HttpMessage::body_with_stats()`
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct BodyStats {
+    /// Number of bytes
+    pub bytes: usize,
+    /// Number of `\n` characters
+    pub lines: usize,
+    /// Number of received chunks
+    pub chunks: usize,
+}
+
+/// Decision about partial response, see `HttpMessage::resolve_range()`
+#[derive(Clone, PartialEq, Debug)]
+pub enum RangeDecision {
+    /// Send full body, request is not a range request or
+    /// *If-Range* condition failed
+    Full,
+    /// Send specified end-inclusive byte ranges
+    Partial(Vec<(u64, u64)>),
+    /// None of the requested ranges is satisfiable, send
+    /// `416 Range Not Satisfiable`
+    NotSatisfiable,
+}
+
+/// Result of content negotiation.
+///
+/// Besides the selected media type it carries the list of request headers
+/// the selection depended on, so the response `Vary` header can be set.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Negotiation {
+    /// Selected media type
+    pub chosen: Mime,
+    /// Request headers that were used during negotiation
+    pub vary: Vec<HeaderName>,
+}
 
 /// Trait that implements general purpose operations on http messages
 pub trait HttpMessage: Sized {
@@ -65,10 +497,36 @@ pub trait HttpMessage: Sized {
         ""
     }
 
+    /// Read top-level type of the request content type, i.e. `image` for
+    /// `image/png`.
+    ///
+    /// Returns `None` if request does not contain *Content-Type* header
+    /// or its value is not a media type.
+    fn content_type_top(&self) -> Option<&str> {
+        let content_type = self.content_type();
+        let idx = content_type.find('/')?;
+        let top = content_type[..idx].trim();
+        if top.is_empty() {
+            None
+        } else {
+            Some(top)
+        }
+    }
+
     /// Get content type encoding
     ///
     /// UTF-8 is used by default, If request charset is not set.
     fn encoding(&self) -> Result<EncodingRef, ContentTypeError> {
+        self.encoding_or(UTF_8)
+    }
+
+    /// Get content type encoding
+    ///
+    /// `default` encoding is used, If request charset is not set.
+    fn encoding_or(
+        &self,
+        default: EncodingRef,
+    ) -> Result<EncodingRef, ContentTypeError> {
         if let Some(mime_type) = self.mime_type()? {
             if let Some(charset) = mime_type.get_param("charset") {
                 if let Some(enc) = encoding_from_whatwg_label(charset.as_str()) {
@@ -77,10 +535,10 @@ pub trait HttpMessage: Sized {
                     Err(ContentTypeError::UnknownEncoding)
                 }
             } else {
-                Ok(UTF_8)
+                Ok(default)
             }
         } else {
-            Ok(UTF_8)
+            Ok(default)
         }
     }
 
@@ -99,827 +557,5878 @@ pub trait HttpMessage: Sized {
         Ok(None)
     }
 
-    /// Check if request has chunked transfer encoding
-    fn chunked(&self) -> Result<bool, ParseError> {
-        if let Some(encodings) = self.headers().get(header::TRANSFER_ENCODING) {
-            if let Ok(s) = encodings.to_str() {
-                Ok(s.to_lowercase().contains("chunked"))
+    /// Parse required content type of the message.
+    ///
+    /// Unlike `mime_type()`, absent *Content-Type* header is reported as
+    /// `ContentTypeError::Missing` error.
+    fn require_content_type(&self) -> Result<Mime, ContentTypeError> {
+        self.mime_type()?.ok_or(ContentTypeError::Missing)
+    }
+
+    /// Select the best media type from `available` according to the
+    /// request's *Accept* header.
+    ///
+    /// If the request does not contain *Accept* header, first available
+    /// media type get returned. `None` means that none of the available
+    /// types is acceptable.
+    fn negotiate(&self, available: &[Mime]) -> Option<Mime> {
+        self.negotiation(available).map(|n| n.chosen)
+    }
+
+    /// Same as `negotiate()`, but also returns headers the selection
+    /// depended on. Use `Negotiation::vary` to set response's *Vary* header.
+    fn negotiation(&self, available: &[Mime]) -> Option<Negotiation> {
+        let accept = self.get_header::<Accept>();
+
+        let mut best: Option<(&Mime, Quality)> = None;
+        for mime in available {
+            let quality = if let Some(ref accept) = accept {
+                match accept_quality(&accept.0, mime) {
+                    Some(quality) => quality,
+                    None => continue,
+                }
             } else {
-                Err(ParseError::Header)
+                Quality::default()
+            };
+            if quality == q(0) {
+                continue;
+            }
+            if best.map(|(_, prev)| quality > prev).unwrap_or(true) {
+                best = Some((mime, quality));
             }
-        } else {
-            Ok(false)
         }
+
+        best.map(|(mime, _)| Negotiation {
+            chosen: mime.clone(),
+            vary: vec![header::ACCEPT],
+        })
     }
 
-    /// Load request cookies.
-    #[inline]
-    fn cookies(&self) -> Result<Ref<Vec<Cookie<'static>>>, CookieParseError> {
-        if self.extensions().get::<Cookies>().is_none() {
-            let mut cookies = Vec::new();
-            for hdr in self.headers().get_all(header::COOKIE) {
-                let s =
-                    str::from_utf8(hdr.as_bytes()).map_err(CookieParseError::from)?;
-                for cookie_str in s.split(';').map(|s| s.trim()) {
-                    if !cookie_str.is_empty() {
-                        cookies.push(Cookie::parse_encoded(cookie_str)?.into_owned());
-                    }
+    /// Select the best charset from `available` according to the
+    /// request's *Accept-Charset* header.
+    ///
+    /// Charsets are compared case-insensitively, `*` matches any charset
+    /// not listed explicitly. Utf-8 is always acceptable with default
+    /// quality unless it is listed explicitly. If the request does not contain
+    /// *Accept-Charset* header, first available charset get returned.
+    /// `None` means that none of the available charsets is acceptable.
+    fn negotiate_charset<'a>(&self, available: &[&'a str]) -> Option<&'a str> {
+        let accept = match self.get_header::<AcceptCharset>() {
+            Some(accept) => accept,
+            None => return available.first().cloned(),
+        };
+
+        let mut best: Option<(&str, Quality)> = None;
+        for charset in available {
+            let mut exact = None;
+            let mut wildcard = None;
+            for item in &accept.0 {
+                let label = item.item.to_string();
+                if label.eq_ignore_ascii_case(charset) {
+                    exact = Some(item.quality);
+                } else if label == "*" {
+                    wildcard = Some(item.quality);
                 }
             }
-            self.extensions_mut().insert(Cookies(cookies));
+            let quality = match exact {
+                Some(quality) => quality,
+                None if charset.eq_ignore_ascii_case("utf-8") => Quality::default(),
+                None => match wildcard {
+                    Some(quality) => quality,
+                    None => continue,
+                },
+            };
+            if quality == q(0) {
+                continue;
+            }
+            if best.map(|(_, prev)| quality > prev).unwrap_or(true) {
+                best = Some((charset, quality));
+            }
         }
-        Ok(Ref::map(self.extensions(), |ext| {
-            &ext.get::<Cookies>().unwrap().0
-        }))
+        best.map(|(charset, _)| charset)
     }
 
-    /// Return request cookie.
-    fn cookie(&self, name: &str) -> Option<Cookie<'static>> {
-        if let Ok(cookies) = self.cookies() {
-            for cookie in cookies.iter() {
-                if cookie.name() == name {
-                    return Some(cookie.to_owned());
+    /// Check if client accepts `application/json` response.
+    ///
+    /// Wildcard and quality rules are the same as for `negotiate()`,
+    /// zero quality means not acceptable. If request does not contain
+    /// *Accept* header, `false` get returned.
+    fn wants_json(&self) -> bool {
+        self.get_header::<Accept>()
+            .and_then(|accept| accept_quality(&accept.0, &mime::APPLICATION_JSON))
+            .map(|quality| quality != q(0))
+            .unwrap_or(false)
+    }
+
+    /// Decide whether to send full body or requested byte ranges.
+    ///
+    /// `total_len`, `etag` and `last_modified` describe current
+    /// representation. *Range* header is ignored if it is absent, malformed
+    /// or uses unit other than `bytes`. *If-Range* entity tag condition
+    /// requires strong match, date condition requires exact match with
+    /// `last_modified`.
+    ///
+    /// Overlapping and adjacent ranges are coalesced and returned in
+    /// ascending order. Request with more than 100 ranges gets full body.
+    fn resolve_range(
+        &self,
+        total_len: u64,
+        etag: Option<&EntityTag>,
+        last_modified: Option<HttpDate>,
+    ) -> RangeDecision {
+        let specs = match self.get_header::<Range>() {
+            Some(Range::Bytes(specs)) => specs,
+            _ => return RangeDecision::Full,
+        };
+        if specs.len() > MAX_RANGES {
+            return RangeDecision::Full;
+        }
+
+        if self.headers().contains_key(header::IF_RANGE) {
+            let fresh = match self.get_header::<IfRange>() {
+                Some(IfRange::EntityTag(ref tag)) => {
+                    etag.map(|etag| etag.strong_eq(tag)).unwrap_or(false)
                 }
+                Some(IfRange::Date(date)) => {
+                    last_modified.map(SystemTime::from) == Some(SystemTime::from(date))
+                }
+                None => false,
+            };
+            if !fresh {
+                return RangeDecision::Full;
             }
         }
-        None
+
+        let mut ranges: Vec<(u64, u64)> = specs
+            .iter()
+            .filter_map(|spec| spec.to_satisfiable_range(total_len))
+            .collect();
+        ranges.sort();
+
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1.saturating_add(1) => {
+                    last.1 = last.1.max(end);
+                }
+                _ => merged.push((start, end)),
+            }
+        }
+        if merged.is_empty() {
+            RangeDecision::NotSatisfiable
+        } else {
+            RangeDecision::Partial(merged)
+        }
     }
 
-    /// Load http message body.
+    /// Parse *Accept-Encoding* header.
     ///
-    /// By default only 256Kb payload reads to a memory, then
-    /// `PayloadError::Overflow` get returned. Use `MessageBody::limit()`
-    /// method to change upper limit.
+    /// Returns `None` if header is absent or malformed.
+    fn accept_encoding(&self) -> Option<AcceptEncoding> {
+        self.get_header::<AcceptEncoding>()
+    }
+
+    /// Check if client accepts `gzip` encoded response.
     ///
-    /// ## Server example
+    /// Explicit `gzip` entry takes precedence over `*` wildcard, zero quality
+    /// means not acceptable. If request does not contain *Accept-Encoding*
+    /// header, `false` get returned.
+    fn accepts_gzip(&self) -> bool {
+        accepts_encoding(self.accept_encoding(), &Encoding::Gzip)
+    }
+
+    /// Check if client accepts `br` encoded response, see `accepts_gzip()`.
+    fn accepts_brotli(&self) -> bool {
+        accepts_encoding(self.accept_encoding(), &Encoding::Brotli)
+    }
+
+    /// Check if client accepts `deflate` encoded response,
+    /// see `accepts_gzip()`.
+    fn accepts_deflate(&self) -> bool {
+        accepts_encoding(self.accept_encoding(), &Encoding::Deflate)
+    }
+
+    /// Number of occurrences of the header in the message.
+    fn header_count<K: AsHeaderName>(&self, name: K) -> usize {
+        self.headers().get_all(name).iter().count()
+    }
+
+    /// Check if the header occurs in the message more than once.
     ///
-    /// ```rust,ignore
-    /// # extern crate bytes;
-    /// # extern crate actix_web;
-    /// # extern crate futures;
-    /// # #[macro_use] extern crate serde_derive;
-    /// use actix_web::{
-    ///     AsyncResponder, FutureResponse, HttpMessage, HttpRequest, Response,
-    /// };
-    /// use bytes::Bytes;
-    /// use futures::future::Future;
+    /// Useful for headers that must be unique, i.e. `Host` or
+    /// `Content-Length`.
+    fn has_duplicate_header<K: AsHeaderName>(&self, name: K) -> bool {
+        self.header_count(name) > 1
+    }
+
+    /// Check that all header values contain only printable ASCII and tab.
     ///
-    /// fn index(mut req: HttpRequest) -> FutureResponse<Response> {
-    ///     req.body()                     // <- get Body future
-    ///        .limit(1024)                // <- change max size of the body to a 1kb
-    ///        .from_err()
-    ///        .and_then(|bytes: Bytes| {  // <- complete body
-    ///            println!("==== BODY ==== {:?}", bytes);
-    ///            Ok(Response::Ok().into())
-    ///        }).responder()
-    /// }
-    /// # fn main() {}
-    /// ```
-    fn body(&mut self) -> MessageBody<Self>
-    where
-        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + Sized,
-    {
-        MessageBody::new(self)
+    /// Returns `ParseError::NonAsciiHeader` with name of the first
+    /// offending header.
+    fn validate_ascii_headers(&self) -> Result<(), ParseError> {
+        for (name, value) in self.headers() {
+            if value
+                .as_bytes()
+                .iter()
+                .any(|b| *b != b'\t' && (*b < 0x20 || *b > 0x7e))
+            {
+                return Err(ParseError::NonAsciiHeader(name.clone()));
+            }
+        }
+        Ok(())
     }
 
-    /// Parse `application/x-www-form-urlencoded` encoded request's body.
-    /// Return `UrlEncoded` future. Form can be deserialized to any type that
-    /// implements `Deserialize` trait from *serde*.
+    /// Check that header values do not contain line folding (obs-fold).
     ///
-    /// Returns error:
+    /// Embedded CR or LF and leading whitespace left after unfolding are
+    /// rejected. Returns `ParseError::ObsFold` with name of the first
+    /// offending header.
+    fn reject_obs_fold(&self) -> Result<(), ParseError> {
+        for (name, value) in self.headers() {
+            let value = value.as_bytes();
+            if value.starts_with(b" ")
+                || value.starts_with(b"\t")
+                || value.iter().any(|b| *b == b'\r' || *b == b'\n')
+            {
+                return Err(ParseError::ObsFold(name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that header is present and its value is equal to `expected`.
     ///
-    /// * content type is not `application/x-www-form-urlencoded`
-    /// * content-length is greater than 256k
+    /// Returns `ParseError::MissingHeader` if header is absent and
+    /// `ParseError::HeaderMismatch` if value is different.
+    fn require_header<K: AsHeaderName>(
+        &self,
+        name: K,
+        expected: &str,
+    ) -> Result<(), ParseError> {
+        match self.headers().get(name) {
+            Some(value) if value.as_bytes() == expected.as_bytes() => Ok(()),
+            Some(_) => Err(ParseError::HeaderMismatch),
+            None => Err(ParseError::MissingHeader),
+        }
+    }
+
+    /// Same as `require_header()`, but compares ASCII values case-insensitively.
+    fn require_header_ignore_case<K: AsHeaderName>(
+        &self,
+        name: K,
+        expected: &str,
+    ) -> Result<(), ParseError> {
+        match self.headers().get(name) {
+            Some(value)
+                if value.as_bytes().eq_ignore_ascii_case(expected.as_bytes()) =>
+            {
+                Ok(())
+            }
+            Some(_) => Err(ParseError::HeaderMismatch),
+            None => Err(ParseError::MissingHeader),
+        }
+    }
+
+    /// Message headers with names in canonical title case,
+    /// i.e. `Content-Type`.
     ///
-    /// ## Server example
+    /// Header values are decoded lossily. Useful for human readable dumps.
+    fn headers_titlecase(&self) -> Vec<(String, String)> {
+        self.headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    title_case(name.as_str()),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect()
+    }
+
+    /// Read the request's *Referer* header.
     ///
-    /// ```rust,ignore
-    /// # extern crate actix_web;
-    /// # extern crate futures;
-    /// # use futures::Future;
-    /// # use std::collections::HashMap;
-    /// use actix_web::{FutureResponse, HttpMessage, HttpRequest, Response};
+    /// `None` get returned if header is missing or is not valid utf-8.
+    fn referer(&self) -> Option<&str> {
+        self.headers()
+            .get(header::REFERER)
+            .and_then(|hdr| hdr.to_str().ok())
+    }
+
+    /// Parse the request's *Referer* header as url.
+    fn referer_url(&self) -> Option<Url> {
+        self.referer().and_then(|s| Url::parse(s).ok())
+    }
+
+    /// Read the message's *Content-Location* header.
     ///
-    /// fn index(mut req: HttpRequest) -> FutureResponse<Response> {
-    ///     Box::new(
-    ///         req.urlencoded::<HashMap<String, String>>()  // <- get UrlEncoded future
-    ///            .from_err()
-    ///            .and_then(|params| {  // <- url encoded parameters
-    ///                println!("==== BODY ==== {:?}", params);
-    ///                Ok(Response::Ok().into())
-    ///           }),
-    ///     )
-    /// }
-    /// # fn main() {}
-    /// ```
-    fn urlencoded<T: DeserializeOwned>(&mut self) -> UrlEncoded<Self, T>
-    where
-        Self::Stream: Stream<Item = Bytes, Error = PayloadError>,
-    {
-        UrlEncoded::new(self)
+    /// `None` get returned if header is missing or is not valid utf-8.
+    fn content_location(&self) -> Option<&str> {
+        self.headers()
+            .get(header::CONTENT_LOCATION)
+            .and_then(|hdr| hdr.to_str().ok())
     }
 
-    /// Parse `application/json` encoded body.
-    /// Return `JsonBody<T>` future. It resolves to a `T` value.
-    ///
-    /// Returns error:
-    ///
-    /// * content type is not `application/json`
-    /// * content length is greater than 256k
-    ///
-    /// ## Server example
-    ///
-    /// ```rust,ignore
-    /// # extern crate actix_web;
-    /// # extern crate futures;
-    /// # #[macro_use] extern crate serde_derive;
-    /// use actix_web::*;
-    /// use futures::future::{ok, Future};
+    /// Parse the message's *Content-Location* header as url.
     ///
-    /// #[derive(Deserialize, Debug)]
-    /// struct MyObj {
-    ///     name: String,
-    /// }
+    /// Only absolute urls are parsed, relative reference yields `None`.
+    fn content_location_url(&self) -> Option<Url> {
+        self.content_location().and_then(|s| Url::parse(s).ok())
+    }
+
+    /// List of subprotocols requested in *Sec-WebSocket-Protocol* header.
     ///
-    /// fn index(mut req: HttpRequest) -> Box<Future<Item = Response, Error = Error>> {
-    ///     req.json()                   // <- get JsonBody future
-    ///        .from_err()
-    ///        .and_then(|val: MyObj| {  // <- deserialized value
-    ///            println!("==== BODY ==== {:?}", val);
-    ///            Ok(Response::Ok().into())
-    ///        }).responder()
-    /// }
-    /// # fn main() {}
-    /// ```
-    fn json<T: DeserializeOwned + 'static>(&mut self) -> JsonBody<Self, T>
-    where
-        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
-    {
-        JsonBody::new(self)
+    /// Protocols are returned in the client's order of preference.
+    fn websocket_protocols(&self) -> Vec<String> {
+        self.headers()
+            .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|hdr| hdr.to_str().ok())
+            .flat_map(|s| s.split(','))
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_owned())
+            .collect()
     }
 
-    /// Return stream of lines.
-    fn readlines(&mut self) -> Readlines<Self>
-    where
-        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
-    {
-        Readlines::new(self)
+    /// Select first subprotocol requested by the client that is also
+    /// present in `supported`.
+    fn select_websocket_protocol(&self, supported: &[&str]) -> Option<String> {
+        self.websocket_protocols()
+            .into_iter()
+            .find(|proto| supported.contains(&proto.as_str()))
     }
-}
 
-impl<'a, T> HttpMessage for &'a mut T
-where
-    T: HttpMessage,
-{
-    type Stream = T::Stream;
+    /// Parse entries of *Warning* headers. Malformed entries are skipped.
+    fn warnings(&self) -> Vec<WarningHeader> {
+        WarningHeader::from_headers(self.headers())
+    }
 
-    fn headers(&self) -> &HeaderMap {
-        (**self).headers()
+    /// Read Fetch Metadata request headers (`Sec-Fetch-*`).
+    fn fetch_metadata(&self) -> FetchMetadata {
+        FetchMetadata::from_headers(self.headers())
     }
 
-    /// Message payload stream
-    fn take_payload(&mut self) -> Payload<Self::Stream> {
-        (**self).take_payload()
+    /// Parse AWS Signature Version 4 *Authorization* header.
+    ///
+    /// Returns `None` if header is absent, malformed or uses other scheme.
+    fn aws_sigv4(&self) -> Option<SigV4> {
+        SigV4::from_headers(self.headers())
     }
 
-    /// Request's extensions container
-    fn extensions(&self) -> Ref<Extensions> {
-        (**self).extensions()
+    /// Check if request has chunked transfer encoding
+    fn chunked(&self) -> Result<bool, ParseError> {
+        if let Some(encodings) = self.headers().get(header::TRANSFER_ENCODING) {
+            if let Ok(s) = encodings.to_str() {
+                Ok(s.to_lowercase().contains("chunked"))
+            } else {
+                Err(ParseError::Header)
+            }
+        } else {
+            Ok(false)
+        }
     }
 
-    /// Mutable reference to a the request's extensions container
-    fn extensions_mut(&self) -> RefMut<Extensions> {
-        (**self).extensions_mut()
+    /// Parse all expectations of *Expect* header.
+    ///
+    /// Expectation names are lowercased, optional values are returned with
+    /// surrounding quotes removed. Returns empty vec if header is absent.
+    fn expectations(&self) -> Vec<(String, Option<String>)> {
+        let mut result = Vec::new();
+        for hdr in self.headers().get_all(header::EXPECT) {
+            let value = match hdr.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            for item in value.split(',') {
+                let mut parts = item.splitn(2, '=');
+                let name = parts.next().unwrap_or("").trim();
+                if name.is_empty() {
+                    continue;
+                }
+                let value = parts
+                    .next()
+                    .map(|value| value.trim().trim_matches('"').to_owned());
+                result.push((name.to_ascii_lowercase(), value));
+            }
+        }
+        result
     }
-}
 
-/// Stream to read request line by line.
-pub struct Readlines<T: HttpMessage> {
-    stream: Payload<T::Stream>,
-    buff: BytesMut,
-    limit: usize,
-    checked_buff: bool,
-    encoding: EncodingRef,
-    err: Option<ReadlinesError>,
-}
+    /// Check if request expects `100-continue` response.
+    fn expects_continue(&self) -> bool {
+        self.expectations()
+            .iter()
+            .any(|(name, value)| name == "100-continue" && value.is_none())
+    }
 
-impl<T> Readlines<T>
-where
-    T: HttpMessage,
-    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
-{
-    /// Create a new stream to read request line by line.
-    fn new(req: &mut T) -> Self {
-        let encoding = match req.encoding() {
-            Ok(enc) => enc,
-            Err(err) => return Self::err(err.into()),
-        };
+    /// Check if request carries any of conditional headers.
+    ///
+    /// *If-Match*, *If-None-Match*, *If-Modified-Since*,
+    /// *If-Unmodified-Since* and *If-Range* headers are checked, header
+    /// values are not validated.
+    fn is_conditional(&self) -> bool {
+        let headers = self.headers();
+        headers.contains_key(header::IF_MATCH)
+            || headers.contains_key(header::IF_NONE_MATCH)
+            || headers.contains_key(header::IF_MODIFIED_SINCE)
+            || headers.contains_key(header::IF_UNMODIFIED_SINCE)
+            || headers.contains_key(header::IF_RANGE)
+    }
 
-        Readlines {
-            stream: req.take_payload(),
-            buff: BytesMut::with_capacity(262_144),
-            limit: 262_144,
-            checked_buff: true,
-            err: None,
-            encoding,
+    /// Parse *Keep-Alive* header into `(timeout, max)` parameters.
+    ///
+    /// Returns `None` if header is absent, missing or malformed parameters
+    /// are returned as `None`.
+    fn keep_alive_params(&self) -> Option<(Option<u64>, Option<u64>)> {
+        let value = self.headers().get("keep-alive")?.to_str().ok()?;
+        let (mut timeout, mut max) = (None, None);
+        for param in value.split(',') {
+            let mut parts = param.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+            let value = parts.next().and_then(|v| v.trim().parse().ok());
+            if name.eq_ignore_ascii_case("timeout") {
+                timeout = value;
+            } else if name.eq_ignore_ascii_case("max") {
+                max = value;
+            }
         }
+        Some((timeout, max))
     }
 
-    /// Change max line size. By default max size is 256Kb
-    pub fn limit(mut self, limit: usize) -> Self {
-        self.limit = limit;
-        self
+    /// Check if message body fits the limit, based on *Content-Length* header.
+    ///
+    /// Returns `None` if body length is unknown, i.e. for chunked messages
+    /// or malformed *Content-Length* header. Payload is not touched.
+    fn body_fits(&self, limit: usize) -> Option<bool> {
+        match parse_content_length(self.headers()) {
+            Ok(Some(len)) => Some(len <= limit),
+            _ => None,
+        }
     }
 
-    fn err(err: ReadlinesError) -> Self {
-        Readlines {
-            stream: Payload::None,
-            buff: BytesMut::new(),
-            limit: 262_144,
-            checked_buff: true,
-            encoding: UTF_8,
-            err: Some(err),
+    /// Protocol version of the message.
+    ///
+    /// Returns `None` if message type does not carry protocol version.
+    fn http_version(&self) -> Option<Version> {
+        None
+    }
+
+    /// Check if it is safe to replay the message.
+    ///
+    /// Message is considered retry-safe if it carries *Idempotency-Key*
+    /// header, see `idempotency_key()`. Request overrides this check and
+    /// also takes idempotent methods into account.
+    fn is_retry_safe(&self) -> bool {
+        self.idempotency_key().is_some()
+    }
+
+    /// Read *Idempotency-Key* header value.
+    ///
+    /// Value is trimmed, `None` is returned if header is absent, empty
+    /// or is not a valid string. See `idempotency_key_strict()` for
+    /// validated variant.
+    fn idempotency_key(&self) -> Option<String> {
+        self.headers()
+            .get(IDEMPOTENCY_KEY)
+            .and_then(|key| key.to_str().ok())
+            .map(|key| key.trim())
+            .filter(|key| !key.is_empty())
+            .map(|key| key.to_owned())
+    }
+
+    /// Read and validate *Idempotency-Key* header value.
+    ///
+    /// Key must be at most 255 characters long and consist of visible
+    /// ASCII characters, otherwise `ParseError::Header` get returned.
+    /// Absent header results in `Ok(None)`.
+    fn idempotency_key_strict(&self) -> Result<Option<String>, ParseError> {
+        let key = match self.headers().get(IDEMPOTENCY_KEY) {
+            Some(key) => key.to_str().map_err(|_| ParseError::Header)?.trim(),
+            None => return Ok(None),
+        };
+        if key.is_empty()
+            || key.len() > 255
+            || !key.bytes().all(|b| b.is_ascii_graphic())
+        {
+            return Err(ParseError::Header);
         }
+        Ok(Some(key.to_owned()))
     }
-}
 
-impl<T> Stream for Readlines<T>
-where
-    T: HttpMessage,
-    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
-{
-    type Item = String;
-    type Error = ReadlinesError;
+    /// Load request cookies.
+    ///
+    /// At most `MAX_COOKIES` cookies get parsed, the rest is ignored. Use
+    /// `cookies_limited()` to reject requests with too many cookies.
+    fn cookies(&self) -> Result<Ref<Vec<Cookie<'static>>>, CookieParseError> {
+        let parsed = match self.extensions().get::<Cookies>() {
+            Some(cached) if cached.too_many => cached.limit == MAX_COOKIES,
+            Some(cached) => cached.cookies.len() <= MAX_COOKIES,
+            None => false,
+        };
+        if !parsed {
+            parse_cookies(self, MAX_COOKIES)?;
+        }
+        Ok(Ref::map(self.extensions(), |ext| {
+            &ext.get::<Cookies>().unwrap().cookies
+        }))
+    }
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        if let Some(err) = self.err.take() {
-            return Err(err);
+    /// Load request cookies, parsing at most `max` cookies.
+    ///
+    /// If request contains more cookies, `CookieError::TooMany` get
+    /// returned. Parsed cookies are cached, cache is re-parsed only if
+    /// previous call hit a lower limit.
+    fn cookies_limited(
+        &self,
+        max: usize,
+    ) -> Result<Ref<Vec<Cookie<'static>>>, CookieError> {
+        let parsed = match self.extensions().get::<Cookies>() {
+            Some(cached) => !cached.too_many || cached.limit >= max,
+            None => false,
+        };
+        if !parsed {
+            parse_cookies(self, max)?;
         }
 
-        // check if there is a newline in the buffer
-        if !self.checked_buff {
-            let mut found: Option<usize> = None;
-            for (ind, b) in self.buff.iter().enumerate() {
-                if *b == b'\n' {
-                    found = Some(ind);
-                    break;
-                }
-            }
-            if let Some(ind) = found {
-                // check if line is longer than limit
-                if ind + 1 > self.limit {
-                    return Err(ReadlinesError::LimitOverflow);
-                }
-                let enc: *const Encoding = self.encoding as *const Encoding;
-                let line = if enc == UTF_8 {
-                    str::from_utf8(&self.buff.split_to(ind + 1))
-                        .map_err(|_| ReadlinesError::EncodingError)?
-                        .to_owned()
-                } else {
-                    self.encoding
-                        .decode(&self.buff.split_to(ind + 1), DecoderTrap::Strict)
-                        .map_err(|_| ReadlinesError::EncodingError)?
-                };
-                return Ok(Async::Ready(Some(line)));
-            }
-            self.checked_buff = true;
+        let ext = self.extensions();
+        let too_many = {
+            let cached = ext.get::<Cookies>().unwrap();
+            cached.too_many || cached.cookies.len() > max
+        };
+        if too_many {
+            return Err(CookieError::TooMany);
         }
-        // poll req for more bytes
-        match self.stream.poll() {
-            Ok(Async::Ready(Some(mut bytes))) => {
-                // check if there is a newline in bytes
-                let mut found: Option<usize> = None;
-                for (ind, b) in bytes.iter().enumerate() {
-                    if *b == b'\n' {
-                        found = Some(ind);
-                        break;
-                    }
-                }
-                if let Some(ind) = found {
-                    // check if line is longer than limit
-                    if ind + 1 > self.limit {
-                        return Err(ReadlinesError::LimitOverflow);
+        Ok(Ref::map(ext, |ext| &ext.get::<Cookies>().unwrap().cookies))
+    }
+
+    /// Load request cookies without percent-decoding their values.
+    ///
+    /// Values are preserved exactly as sent, i.e. for forwarding opaque
+    /// tokens. At most `MAX_COOKIES` cookies get parsed, result is not cached.
+    fn cookies_raw(&self) -> Result<Vec<Cookie<'static>>, CookieError> {
+        let mut cookies = Vec::new();
+        for hdr in self.headers().get_all(header::COOKIE) {
+            let s = str::from_utf8(hdr.as_bytes())
+                .map_err(|e| CookieError::Parse(e.into()))?;
+            for cookie_str in s.split(';').map(|s| s.trim()) {
+                if !cookie_str.is_empty() {
+                    if cookies.len() == MAX_COOKIES {
+                        return Err(CookieError::TooMany);
                     }
-                    let enc: *const Encoding = self.encoding as *const Encoding;
-                    let line = if enc == UTF_8 {
-                        str::from_utf8(&bytes.split_to(ind + 1))
-                            .map_err(|_| ReadlinesError::EncodingError)?
-                            .to_owned()
-                    } else {
-                        self.encoding
-                            .decode(&bytes.split_to(ind + 1), DecoderTrap::Strict)
-                            .map_err(|_| ReadlinesError::EncodingError)?
-                    };
-                    // extend buffer with rest of the bytes;
-                    self.buff.extend_from_slice(&bytes);
-                    self.checked_buff = false;
-                    return Ok(Async::Ready(Some(line)));
+                    cookies.push(Cookie::parse(cookie_str)?.into_owned());
                 }
-                self.buff.extend_from_slice(&bytes);
-                Ok(Async::NotReady)
             }
-            Ok(Async::NotReady) => Ok(Async::NotReady),
-            Ok(Async::Ready(None)) => {
-                if self.buff.is_empty() {
-                    return Ok(Async::Ready(None));
-                }
-                if self.buff.len() > self.limit {
-                    return Err(ReadlinesError::LimitOverflow);
+        }
+        Ok(cookies)
+    }
+
+    /// Return request cookie.
+    fn cookie(&self, name: &str) -> Option<Cookie<'static>> {
+        if let Ok(cookies) = self.cookies() {
+            for cookie in cookies.iter() {
+                if cookie.name() == name {
+                    return Some(cookie.to_owned());
                 }
-                let enc: *const Encoding = self.encoding as *const Encoding;
-                let line = if enc == UTF_8 {
-                    str::from_utf8(&self.buff)
-                        .map_err(|_| ReadlinesError::EncodingError)?
-                        .to_owned()
-                } else {
-                    self.encoding
-                        .decode(&self.buff, DecoderTrap::Strict)
-                        .map_err(|_| ReadlinesError::EncodingError)?
-                };
-                self.buff.clear();
-                Ok(Async::Ready(Some(line)))
             }
-            Err(e) => Err(ReadlinesError::from(e)),
         }
+        None
     }
-}
-
-/// Future that resolves to a complete http message body.
-pub struct MessageBody<T: HttpMessage> {
-    limit: usize,
-    length: Option<usize>,
-    stream: Payload<T::Stream>,
-    err: Option<PayloadError>,
-    fut: Option<Box<Future<Item = Bytes, Error = PayloadError>>>,
-}
 
-impl<T> MessageBody<T>
-where
-    T: HttpMessage,
-    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
-{
-    /// Create `MessageBody` for request.
-    pub fn new(req: &mut T) -> MessageBody<T> {
-        let mut len = None;
-        if let Some(l) = req.headers().get(header::CONTENT_LENGTH) {
-            if let Ok(s) = l.to_str() {
-                if let Ok(l) = s.parse::<usize>() {
-                    len = Some(l)
-                } else {
-                    return Self::err(PayloadError::UnknownLength);
+    /// Load request cookies with duplicate names collapsed.
+    ///
+    /// `policy` selects which of the cookies with the same name is kept.
+    /// Cookies are returned in order of the first occurrence of the name.
+    fn cookies_dedup(
+        &self,
+        policy: DedupPolicy,
+    ) -> Result<Vec<Cookie<'static>>, CookieParseError> {
+        let cookies = self.cookies()?;
+        let mut result: Vec<Cookie<'static>> = Vec::with_capacity(cookies.len());
+        for cookie in cookies.iter() {
+            match result.iter().position(|c| c.name() == cookie.name()) {
+                Some(idx) => {
+                    if policy == DedupPolicy::LastWins {
+                        result[idx] = cookie.clone();
+                    }
                 }
-            } else {
-                return Self::err(PayloadError::UnknownLength);
+                None => result.push(cookie.clone()),
             }
         }
+        Ok(result)
+    }
 
-        MessageBody {
-            stream: req.take_payload(),
-            limit: 262_144,
-            length: len,
+    /// Return raw values of all *Cookie* headers, without parsing.
+    ///
+    /// Values that are not valid utf-8 are skipped.
+    fn raw_cookie_headers(&self) -> Vec<&str> {
+        self.headers()
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|hdr| str::from_utf8(hdr.as_bytes()).ok())
+            .collect()
+    }
+
+    /// Return all request cookies which name starts with `prefix`.
+    fn cookies_with_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<Cookie<'static>>, CookieParseError> {
+        Ok(self
+            .cookies()?
+            .iter()
+            .filter(|cookie| cookie.name().starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+
+    /// Load http message body.
+    ///
+    /// By default only 256Kb payload reads to a memory, then
+    /// `PayloadError::Overflow` get returned. Use `MessageBody::limit()`
+    /// method to change upper limit.
+    ///
+    /// ## Server example
+    ///
+    /// ```rust,ignore
+    /// # extern crate bytes;
+    /// # extern crate actix_web;
+    /// # extern crate futures;
+    /// # #[macro_use] extern crate serde_derive;
+    /// use actix_web::{
+    ///     AsyncResponder, FutureResponse, HttpMessage, HttpRequest, Response,
+    /// };
+    /// use bytes::Bytes;
+    /// use futures::future::Future;
+    ///
+    /// fn index(mut req: HttpRequest) -> FutureResponse<Response> {
+    ///     req.body()                     // <- get Body future
+    ///        .limit(1024)                // <- change max size of the body to a 1kb
+    ///        .from_err()
+    ///        .and_then(|bytes: Bytes| {  // <- complete body
+    ///            println!("==== BODY ==== {:?}", bytes);
+    ///            Ok(Response::Ok().into())
+    ///        }).responder()
+    /// }
+    /// # fn main() {}
+    /// ```
+    fn body(&mut self) -> MessageBody<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + Sized,
+    {
+        MessageBody::new(self)
+    }
+
+    /// Load http message body within total request size limit.
+    ///
+    /// Approximate header size, sum of names and values lengths, is
+    /// subtracted from `max` and the rest is used as body size limit. If
+    /// headers alone exceed `max`, `PayloadError::Overflow` get returned.
+    fn total_size_guard(&mut self, max: usize) -> MessageBody<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + Sized,
+    {
+        let headers_size: usize = self
+            .headers()
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+        if headers_size > max {
+            return MessageBody::err(PayloadError::Overflow);
+        }
+        MessageBody::new(self).limit(max - headers_size)
+    }
+
+    /// Load http message body that may be absent.
+    ///
+    /// Resolves to `None` if message has no payload or payload stream ends
+    /// without any data. Body size is limited to 256Kb.
+    fn body_optional(
+        &mut self,
+    ) -> Box<Future<Item = Option<Bytes>, Error = PayloadError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(
+            self.body()
+                .map(|body| if body.is_empty() { None } else { Some(body) }),
+        )
+    }
+
+    /// Check that http message body is well-formed json.
+    ///
+    /// Body is validated syntactically, without deserialization to a
+    /// particular type. Resolves to `false` without reading the body if
+    /// content type is not json, payload remains in the message. Body size
+    /// is limited to 256Kb.
+    fn json_is_valid(&mut self) -> Box<Future<Item = bool, Error = PayloadError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        let json = if let Ok(Some(mime)) = self.mime_type() {
+            mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON)
+        } else {
+            false
+        };
+        if !json {
+            return Box::new(ok(false));
+        }
+
+        Box::new(
+            self.body()
+                .map(|body| serde_json::from_slice::<IgnoredAny>(&body).is_ok()),
+        )
+    }
+
+    /// Load http message body and deserialize it either as json or msgpack.
+    ///
+    /// Format is detected by the first significant byte of the body: `{` or
+    /// `[` means json, msgpack map or array marker means msgpack. Content
+    /// type is consulted only if the body is inconclusive. Body size is
+    /// limited to 256Kb.
+    fn sniff_and_parse<T>(&mut self) -> Box<Future<Item = T, Error = SniffError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+        T: DeserializeOwned + 'static,
+    {
+        let content_type = self.content_type().to_owned();
+        Box::new(self.body().from_err::<SniffError>().and_then(move |body| {
+            match sniff_format(&body, &content_type) {
+                Some(BodyFormat::Json) => Ok(serde_json::from_slice::<T>(&body)?),
+                Some(BodyFormat::MsgPack) => Ok(rmp_serde::from_slice::<T>(&body)?),
+                None => Err(SniffError::UnknownFormat),
+            }
+        }))
+    }
+
+    /// Load http message body and strictly validate it as utf-8.
+    ///
+    /// Unlike charset aware decoding, invalid utf-8 sequence is never
+    /// replaced and resolves to `PayloadError::Utf8` error. Body size is
+    /// limited to 256Kb.
+    fn body_utf8(&mut self) -> Box<Future<Item = String, Error = PayloadError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(self.body().and_then(|body| {
+            String::from_utf8(body.to_vec())
+                .map_err(|e| PayloadError::Utf8(e.utf8_error()))
+        }))
+    }
+
+    /// Load http message body as utf-8 string, replacing invalid
+    /// sequences with `U+FFFD` replacement character.
+    ///
+    /// Fails only on payload errors. Body size is limited to 256Kb.
+    fn body_string_lossy(&mut self) -> Box<Future<Item = String, Error = PayloadError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(
+            self.body()
+                .map(|body| String::from_utf8_lossy(&body).into_owned()),
+        )
+    }
+
+    /// Load http message body and transcode it to utf-8 string.
+    ///
+    /// Body is decoded with charset declared in *Content-Type* header, utf-8
+    /// is used by default. Unknown charset or malformed sequence resolves to
+    /// `PayloadError::Charset` error. Body size is limited to 256Kb.
+    fn body_utf8_transcoded(
+        &mut self,
+    ) -> Box<Future<Item = String, Error = PayloadError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        let encoding = match self.encoding() {
+            Ok(enc) => enc,
+            Err(_) => return Box::new(err(PayloadError::Charset)),
+        };
+        Box::new(self.body().and_then(move |body| {
+            encoding
+                .decode(&body, DecoderTrap::Strict)
+                .map_err(|_| PayloadError::Charset)
+        }))
+    }
+
+    /// Load http message body together with request cookies.
+    ///
+    /// Cookies are taken from the cached `cookies()` result. Cookie parse
+    /// errors do not fail the future, empty cookie list is returned instead.
+    /// Body size is limited to 256Kb.
+    fn body_with_cookies(
+        &mut self,
+    ) -> Box<Future<Item = (Bytes, Vec<Cookie<'static>>), Error = PayloadError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        let cookies = self
+            .cookies()
+            .map(|cookies| cookies.clone())
+            .unwrap_or_default();
+        Box::new(self.body().map(move |body| (body, cookies)))
+    }
+
+    /// Load http message body and verify it against *Content-MD5* header.
+    ///
+    /// Resolves to `PayloadError::ChecksumMismatch` if digest of the body
+    /// does not match the header value. If request does not contain
+    /// *Content-MD5* header, it behaves like `body()`.
+    fn verify_content_md5(&mut self) -> Box<Future<Item = Bytes, Error = PayloadError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        let expected = match self.headers().get("content-md5") {
+            Some(hdr) => match base64::decode(hdr.as_bytes()) {
+                Ok(digest) => Some(digest),
+                Err(_) => return Box::new(err(PayloadError::ChecksumMismatch)),
+            },
+            None => None,
+        };
+
+        Box::new(self.body().and_then(move |body| {
+            if let Some(expected) = expected {
+                if md5::compute(&body).0[..] != expected[..] {
+                    return Err(PayloadError::ChecksumMismatch);
+                }
+            }
+            Ok(body)
+        }))
+    }
+
+    /// Load http message body as a list of received chunks.
+    ///
+    /// Chunks are not concatenated, so no large contiguous buffer get
+    /// allocated. By default total size of the chunks is limited to 256Kb,
+    /// use `BodySegments::limit()` to change it.
+    fn body_segments(&mut self) -> BodySegments<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    {
+        BodySegments::new(self)
+    }
+
+    /// Load http message body and verify it against *Digest* header.
+    ///
+    /// First header entry with supported algorithm (`SHA-256` or `SHA-512`)
+    /// is used. Resolves to `PayloadError::ChecksumMismatch` if digest of the
+    /// body does not match or header does not contain supported entry.
+    /// If request does not contain *Digest* header, it behaves like `body()`.
+    fn verify_digest(&mut self) -> Box<Future<Item = Bytes, Error = PayloadError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        let expected = match self.headers().get("digest") {
+            Some(hdr) => match hdr.to_str().ok().and_then(parse_digest) {
+                Some(digest) => Some(digest),
+                None => return Box::new(err(PayloadError::ChecksumMismatch)),
+            },
+            None => None,
+        };
+
+        Box::new(self.body().and_then(move |body| {
+            if let Some((algo, expected)) = expected {
+                if algo.digest(&body) != expected {
+                    return Err(PayloadError::ChecksumMismatch);
+                }
+            }
+            Ok(body)
+        }))
+    }
+
+    /// Load http message body and verify it against `expected` hash.
+    ///
+    /// Body is hashed while it is collected. Resolves to
+    /// `PayloadError::ChecksumMismatch` if hash of the body differs from
+    /// `expected`. Body size is limited to 256Kb, use
+    /// `body_expect_hash_limited()` to change the limit.
+    fn body_expect_hash(
+        &mut self,
+        algo: HashAlgo,
+        expected: &[u8],
+    ) -> Box<Future<Item = Bytes, Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        self.body_expect_hash_limited(algo, expected, 262_144)
+    }
+
+    /// Same as `body_expect_hash()`, but body size is limited to `limit`
+    /// bytes.
+    fn body_expect_hash_limited(
+        &mut self,
+        algo: HashAlgo,
+        expected: &[u8],
+        limit: usize,
+    ) -> Box<Future<Item = Bytes, Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        let expected = expected.to_vec();
+        Box::new(
+            fold_payload(
+                self,
+                limit,
+                (BytesMut::with_capacity(8192), algo.hasher()),
+                |(mut body, mut hasher), chunk| {
+                    hasher.input(&chunk);
+                    body.extend_from_slice(&chunk);
+                    Ok((body, hasher))
+                },
+            )
+            .and_then(move |(body, hasher)| {
+                if hasher.result() != expected {
+                    return Err(PayloadError::ChecksumMismatch);
+                }
+                Ok(body.freeze())
+            }),
+        )
+    }
+
+    /// Load http message body and verify its HMAC signature.
+    ///
+    /// Signature is read from `header` as hex string, optionally prefixed
+    /// with algorithm name, i.e. `sha256=<hex>`. Body is signed while it is
+    /// collected and signatures are compared in constant time. Missing or
+    /// malformed header and signature mismatch resolve to
+    /// `PayloadError::ChecksumMismatch` error. Body size is limited to 256Kb,
+    /// use `verify_hmac_limited()` to change the limit.
+    fn verify_hmac(
+        &mut self,
+        header: &HeaderName,
+        key: &[u8],
+        algo: HmacAlgo,
+    ) -> Box<Future<Item = Bytes, Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        self.verify_hmac_limited(header, key, algo, 262_144)
+    }
+
+    /// Same as `verify_hmac()`, but body size is limited to `limit` bytes.
+    fn verify_hmac_limited(
+        &mut self,
+        header: &HeaderName,
+        key: &[u8],
+        algo: HmacAlgo,
+        limit: usize,
+    ) -> Box<Future<Item = Bytes, Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        let expected = match self
+            .headers()
+            .get(header)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_signature)
+        {
+            Some(expected) => expected,
+            None => return Box::new(err(PayloadError::ChecksumMismatch)),
+        };
+        Box::new(
+            fold_payload(
+                self,
+                limit,
+                (BytesMut::with_capacity(8192), algo.mac(key)),
+                |(mut body, mut mac), chunk| {
+                    mac.input(&chunk);
+                    body.extend_from_slice(&chunk);
+                    Ok((body, mac))
+                },
+            )
+            .and_then(move |(body, mac)| {
+                if !mac.verify(&expected) {
+                    return Err(PayloadError::ChecksumMismatch);
+                }
+                Ok(body.freeze())
+            }),
+        )
+    }
+
+    /// Load http message body and compute its Shannon byte entropy.
+    ///
+    /// Entropy is measured in bits per byte, in `0.0..=8.0` range, and is
+    /// computed while body is collected. Body size is limited to 256Kb, use
+    /// `body_with_entropy_limited()` to change the limit.
+    fn body_with_entropy(
+        &mut self,
+    ) -> Box<Future<Item = (Bytes, f64), Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        self.body_with_entropy_limited(262_144)
+    }
+
+    /// Same as `body_with_entropy()`, but body size is limited to `limit`
+    /// bytes.
+    fn body_with_entropy_limited(
+        &mut self,
+        limit: usize,
+    ) -> Box<Future<Item = (Bytes, f64), Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(
+            fold_payload(
+                self,
+                limit,
+                (BytesMut::with_capacity(8192), [0usize; 256]),
+                |(mut body, mut counts), chunk| {
+                    for b in chunk.iter() {
+                        counts[*b as usize] += 1;
+                    }
+                    body.extend_from_slice(&chunk);
+                    Ok((body, counts))
+                },
+            )
+            .map(|(body, counts)| {
+                let len = body.len() as f64;
+                let entropy = counts
+                    .iter()
+                    .filter(|count| **count > 0)
+                    .map(|count| {
+                        let p = *count as f64 / len;
+                        -p * p.log2()
+                    })
+                    .sum();
+                (body.freeze(), entropy)
+            }),
+        )
+    }
+
+    /// Load http message body and decode it as quoted-printable.
+    ///
+    /// Soft line breaks are removed and `=XX` escapes are decoded, malformed
+    /// escape sequence resolves to `PayloadError::EncodingCorrupted`. Decoded
+    /// body size is limited to 256Kb.
+    fn body_quoted_printable(
+        &mut self,
+    ) -> Box<Future<Item = Bytes, Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(
+            self.take_payload()
+                .fold(
+                    (BytesMut::with_capacity(8192), BytesMut::new()),
+                    |(mut body, mut buf), chunk| {
+                        buf.extend_from_slice(&chunk);
+                        let consumed = decode_quoted_printable(&buf, &mut body, false)?;
+                        buf.split_to(consumed);
+                        if body.len() > 262_144 {
+                            return Err(PayloadError::Overflow);
+                        }
+                        Ok((body, buf))
+                    },
+                )
+                .and_then(|(mut body, buf)| {
+                    decode_quoted_printable(&buf, &mut body, true)?;
+                    if body.len() > 262_144 {
+                        return Err(PayloadError::Overflow);
+                    }
+                    Ok(body.freeze())
+                }),
+        )
+    }
+
+    /// Load http message body and reject it if it contains NUL byte.
+    ///
+    /// Resolves to `PayloadError::InvalidByte` as soon as chunk with NUL
+    /// byte is received. Body size is limited to 256Kb, use
+    /// `body_no_nul_limited()` to change the limit.
+    fn body_no_nul(&mut self) -> Box<Future<Item = Bytes, Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        self.body_no_nul_limited(262_144)
+    }
+
+    /// Same as `body_no_nul()`, but body size is limited to `limit` bytes.
+    fn body_no_nul_limited(
+        &mut self,
+        limit: usize,
+    ) -> Box<Future<Item = Bytes, Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(
+            fold_payload(
+                self,
+                limit,
+                BytesMut::with_capacity(8192),
+                |mut body, chunk| {
+                    if chunk.contains(&0) {
+                        return Err(PayloadError::InvalidByte);
+                    }
+                    body.extend_from_slice(&chunk);
+                    Ok(body)
+                },
+            )
+            .map(|body| body.freeze()),
+        )
+    }
+
+    /// Load http message body and collect `BodyStats` in the same pass.
+    ///
+    /// Body size is limited to 256Kb, use `body_with_stats_limited()` to
+    /// change the limit.
+    fn body_with_stats(
+        &mut self,
+    ) -> Box<Future<Item = (Bytes, BodyStats), Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        self.body_with_stats_limited(262_144)
+    }
+
+    /// Same as `body_with_stats()`, but body size is limited to `limit`
+    /// bytes.
+    fn body_with_stats_limited(
+        &mut self,
+        limit: usize,
+    ) -> Box<Future<Item = (Bytes, BodyStats), Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(
+            fold_payload(
+                self,
+                limit,
+                (BytesMut::with_capacity(8192), BodyStats::default()),
+                |(mut body, mut stats), chunk| {
+                    stats.bytes += chunk.len();
+                    stats.lines += chunk.iter().filter(|b| **b == b'\n').count();
+                    stats.chunks += 1;
+                    body.extend_from_slice(&chunk);
+                    Ok((body, stats))
+                },
+            )
+            .map(|(body, stats)| (body.freeze(), stats)),
+        )
+    }
+
+    /// Load http message body chunks with their arrival time.
+    ///
+    /// Each chunk is paired with the instant it was received. Total body
+    /// size is limited to 256Kb, use `body_timed_limited()` to change
+    /// the limit.
+    fn body_timed(
+        &mut self,
+    ) -> Box<Future<Item = Vec<(Bytes, Instant)>, Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        self.body_timed_limited(262_144)
+    }
+
+    /// Same as `body_timed()`, but total body size is limited to `limit`
+    /// bytes.
+    fn body_timed_limited(
+        &mut self,
+        limit: usize,
+    ) -> Box<Future<Item = Vec<(Bytes, Instant)>, Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        fold_payload(self, limit, Vec::new(), |mut chunks, chunk| {
+            chunks.push((chunk, Instant::now()));
+            Ok(chunks)
+        })
+    }
+
+    /// Forward message payload to the `sink` chunk by chunk.
+    ///
+    /// Future resolves to the number of forwarded bytes. Sink is dropped
+    /// once payload is forwarded.
+    fn forward_payload<S>(
+        &mut self,
+        sink: S,
+    ) -> Box<Future<Item = usize, Error = ForwardError<S::SinkError>>>
+    where
+        S: Sink<SinkItem = Bytes> + 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(
+            self.take_payload()
+                .map_err(ForwardError::Payload)
+                .fold((sink, 0), |(sink, size), chunk| {
+                    let len = chunk.len();
+                    sink.send(chunk)
+                        .map(move |sink| (sink, size + len))
+                        .map_err(ForwardError::Sink)
+                })
+                .map(|(_, size)| size),
+        )
+    }
+
+    /// Count lines of http message body without loading it to a memory.
+    ///
+    /// Final line without trailing newline is counted as well, so result
+    /// is the same as `str::lines().count()`. If payload size exceeds `limit`,
+    /// `PayloadError::Overflow` get returned.
+    fn count_lines(
+        &mut self,
+        limit: usize,
+    ) -> Box<Future<Item = usize, Error = PayloadError>>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(
+            self.take_payload()
+                .fold((0, 0, None), move |(lines, size, last), chunk| {
+                    let size = size + chunk.len();
+                    if size > limit {
+                        return Err(PayloadError::Overflow);
+                    }
+                    let lines = lines + chunk.iter().filter(|b| **b == b'\n').count();
+                    Ok((lines, size, chunk.last().cloned().or(last)))
+                })
+                .map(|(lines, _, last): (usize, usize, Option<u8>)| match last {
+                    Some(b'\n') | None => lines,
+                    Some(_) => lines + 1,
+                }),
+        )
+    }
+
+    /// Parse `application/x-www-form-urlencoded` encoded request's body.
+    /// Return `UrlEncoded` future. Form can be deserialized to any type that
+    /// implements `Deserialize` trait from *serde*.
+    ///
+    /// Returns error:
+    ///
+    /// * content type is not `application/x-www-form-urlencoded`
+    /// * content-length is greater than 256k
+    ///
+    /// ## Server example
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web;
+    /// # extern crate futures;
+    /// # use futures::Future;
+    /// # use std::collections::HashMap;
+    /// use actix_web::{FutureResponse, HttpMessage, HttpRequest, Response};
+    ///
+    /// fn index(mut req: HttpRequest) -> FutureResponse<Response> {
+    ///     Box::new(
+    ///         req.urlencoded::<HashMap<String, String>>()  // <- get UrlEncoded future
+    ///            .from_err()
+    ///            .and_then(|params| {  // <- url encoded parameters
+    ///                println!("==== BODY ==== {:?}", params);
+    ///                Ok(Response::Ok().into())
+    ///           }),
+    ///     )
+    /// }
+    /// # fn main() {}
+    /// ```
+    fn urlencoded<T: DeserializeOwned>(&mut self) -> UrlEncoded<Self, T>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    {
+        UrlEncoded::new(self)
+    }
+
+    /// Find value of a single field of `application/x-www-form-urlencoded`
+    /// encoded request's body.
+    ///
+    /// Body is parsed incrementally and reading stops as soon as the first
+    /// field with matching name is found. Future resolves to `None` if body
+    /// does not contain the field.
+    fn form_field(&mut self, name: &str) -> FormField<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    {
+        FormField::new(self, name)
+    }
+
+    /// Extract CSRF token from the request.
+    ///
+    /// Token is taken from `header_name` header first. Only if the header is
+    /// absent and `form_field` is provided, urlencoded body is read to find
+    /// the field, so payload is left untouched whenever header is present.
+    fn csrf_token(
+        &mut self,
+        header_name: &str,
+        form_field: Option<&str>,
+    ) -> Box<Future<Item = Option<String>, Error = UrlencodedError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        if let Some(hdr) = self.headers().get(header_name) {
+            return Box::new(ok(hdr.to_str().ok().map(|s| s.to_owned())));
+        }
+        match form_field {
+            Some(name) => Box::new(self.form_field(name)),
+            None => Box::new(ok(None)),
+        }
+    }
+
+    /// Parse `application/json` encoded body.
+    /// Return `JsonBody<T>` future. It resolves to a `T` value.
+    ///
+    /// Returns error:
+    ///
+    /// * content type is not `application/json`
+    /// * content length is greater than 256k
+    ///
+    /// ## Server example
+    ///
+    /// ```rust,ignore
+    /// # extern crate actix_web;
+    /// # extern crate futures;
+    /// # #[macro_use] extern crate serde_derive;
+    /// use actix_web::*;
+    /// use futures::future::{ok, Future};
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// struct MyObj {
+    ///     name: String,
+    /// }
+    ///
+    /// fn index(mut req: HttpRequest) -> Box<Future<Item = Response, Error = Error>> {
+    ///     req.json()                   // <- get JsonBody future
+    ///        .from_err()
+    ///        .and_then(|val: MyObj| {  // <- deserialized value
+    ///            println!("==== BODY ==== {:?}", val);
+    ///            Ok(Response::Ok().into())
+    ///        }).responder()
+    /// }
+    /// # fn main() {}
+    /// ```
+    fn json<T: DeserializeOwned + 'static>(&mut self) -> JsonBody<Self, T>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        JsonBody::new(self)
+    }
+
+    /// Parse content security policy violation report.
+    ///
+    /// Both `application/csp-report` and `application/reports+json` content
+    /// types are accepted, for the latter the first `csp-violation` report
+    /// of the batch is returned. Body size is limited to 256Kb.
+    fn csp_report(&mut self) -> Box<Future<Item = CspReport, Error = JsonPayloadError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        let content_type = self.content_type().to_lowercase();
+        if content_type == "application/csp-report" {
+            let fut = self.json().lenient_content_type(self);
+            Box::new(fut.map(CspReport::from_csp_report))
+        } else if content_type == "application/reports+json" {
+            Box::new(self.json().and_then(CspReport::from_reports))
+        } else {
+            Box::new(err(JsonPayloadError::ContentType))
+        }
+    }
+
+    /// Parse `application/json` encoded body that contains a json array.
+    /// Return `JsonArrayStream<T>` stream that yields array elements as soon
+    /// as they are received, without loading the whole body to a memory.
+    fn json_array_stream<T: DeserializeOwned>(&mut self) -> JsonArrayStream<Self, T>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    {
+        JsonArrayStream::new(self)
+    }
+
+    /// Take message payload as boxed stream detached from the message.
+    ///
+    /// Returned stream is `'static` and can outlive the message, i.e. it can
+    /// be passed to a spawned future. Stream is not `Send`, http/1 payload
+    /// is shared with the dispatcher, so it must be consumed on the same
+    /// thread (use `actix_rt::spawn`).
+    fn take_payload_owned(&mut self) -> PayloadStream
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(self.take_payload())
+    }
+
+    /// Return stream of lines.
+    fn readlines(&mut self) -> Readlines<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Readlines::new(self)
+    }
+
+    /// Return stream of server-sent events of `text/event-stream` body.
+    ///
+    /// Events are terminated by blank line, comments and unknown
+    /// fields are ignored. See `SseEvents` for details.
+    fn sse_events(&mut self) -> SseEvents<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        SseEvents::new(self)
+    }
+
+    /// Return `AsyncRead` reader of the body.
+    ///
+    /// Payload errors are converted to `io::Error`. If payload size exceeds
+    /// reader's limit (256Kb by default), `io::ErrorKind::InvalidData`
+    /// error get returned.
+    fn body_reader(&mut self) -> BodyReader<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    {
+        BodyReader::new(self)
+    }
+
+    /// Return stream of utf-8 validated body chunks.
+    ///
+    /// Multibyte sequences split between payload chunks are buffered, so
+    /// each item contains only complete characters. Invalid sequence is
+    /// reported as `PayloadError::Utf8` error.
+    fn body_str_chunks(&mut self) -> Utf8Chunks<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    {
+        Utf8Chunks::new(self)
+    }
+
+    /// Return stream of records separated by multi-byte `delimiter`.
+    ///
+    /// Records are yielded without the delimiter, total payload size is
+    /// limited to `limit`. See `SplitBody` for details.
+    ///
+    /// Panics if `delimiter` is empty.
+    fn split_body(&mut self, delimiter: &[u8], limit: usize) -> SplitBody<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    {
+        SplitBody::new(self, delimiter, limit)
+    }
+
+    /// Read message body up to the first occurrence of `marker`.
+    ///
+    /// Future resolves to bytes preceding the marker and the rest of
+    /// the payload positioned right after the marker. See `BodyUntil`
+    /// for details.
+    ///
+    /// Panics if `marker` is empty.
+    fn body_until(&mut self, marker: &[u8], limit: usize) -> BodyUntil<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        BodyUntil::new(self, marker, limit)
+    }
+
+    /// Read embedded http-like message from the body.
+    ///
+    /// Header block is terminated by empty line (`\r\n\r\n`), it is parsed
+    /// into `HeaderMap` and the rest of the body is returned as content.
+    /// At most 96 headers are allowed, total size is limited to 256Kb.
+    fn split_headers_body(
+        &mut self,
+    ) -> Box<Future<Item = (HeaderMap, Bytes), Error = ParseError>>
+    where
+        Self: 'static,
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        Box::new(
+            self.body_until(b"\r\n\r\n", 262_144)
+                .from_err::<ParseError>()
+                .and_then(|(head, rest)| {
+                    let headers = parse_header_block(&head)?;
+                    let limit = 262_144 - head.len();
+                    Ok(rest
+                        .fold(BytesMut::new(), move |mut body, chunk| {
+                            if (body.len() + chunk.len()) > limit {
+                                return Err(PayloadError::Overflow);
+                            }
+                            body.extend_from_slice(&chunk);
+                            Ok(body)
+                        })
+                        .from_err::<ParseError>()
+                        .map(move |body| (headers, body.freeze())))
+                })
+                .flatten(),
+        )
+    }
+
+    /// Return stream of deserialized csv records.
+    ///
+    /// Records are parsed incrementally as payload chunks arrive, first
+    /// record is used as a header row by default. See `CsvStream` for details.
+    fn csv_stream<T: DeserializeOwned>(&mut self) -> CsvStream<Self, T>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    {
+        CsvStream::new(self)
+    }
+
+    /// Return stream of length prefixed binary frames.
+    ///
+    /// Each frame is prefixed with its length encoded according to `prefix`,
+    /// stream yields frame data without the prefix.
+    fn length_prefixed_frames(
+        &mut self,
+        prefix: LengthPrefix,
+    ) -> LengthPrefixedFrames<Self>
+    where
+        Self::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    {
+        LengthPrefixedFrames::new(self, prefix)
+    }
+}
+
+impl<'a, T> HttpMessage for &'a mut T
+where
+    T: HttpMessage,
+{
+    type Stream = T::Stream;
+
+    fn headers(&self) -> &HeaderMap {
+        (**self).headers()
+    }
+
+    fn http_version(&self) -> Option<Version> {
+        (**self).http_version()
+    }
+
+    fn is_retry_safe(&self) -> bool {
+        (**self).is_retry_safe()
+    }
+
+    /// Message payload stream
+    fn take_payload(&mut self) -> Payload<Self::Stream> {
+        (**self).take_payload()
+    }
+
+    /// Request's extensions container
+    fn extensions(&self) -> Ref<Extensions> {
+        (**self).extensions()
+    }
+
+    /// Mutable reference to a the request's extensions container
+    fn extensions_mut(&self) -> RefMut<Extensions> {
+        (**self).extensions_mut()
+    }
+}
+
+/// Convert header name to title case, i.e. `content-type` to `Content-Type`
+fn title_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut upper = true;
+    for c in name.chars() {
+        if upper {
+            result.push(c.to_ascii_uppercase());
+        } else {
+            result.push(c);
+        }
+        upper = c == '-';
+    }
+    result
+}
+
+/// Quality of the most specific media range that matches `mime`
+fn accept_quality(accept: &[QualityItem<Mime>], mime: &Mime) -> Option<Quality> {
+    let mut best: Option<(u8, Quality)> = None;
+    for item in accept {
+        let range = &item.item;
+        let specificity = if range.type_() == mime::STAR {
+            0
+        } else if range.type_() != mime.type_() {
+            continue;
+        } else if range.subtype() == mime::STAR {
+            1
+        } else if range.subtype() == mime.subtype() {
+            2
+        } else {
+            continue;
+        };
+        if best.map(|(s, _)| specificity > s).unwrap_or(true) {
+            best = Some((specificity, item.quality));
+        }
+    }
+    best.map(|(_, quality)| quality)
+}
+
+fn accepts_encoding(accept: Option<AcceptEncoding>, encoding: &Encoding) -> bool {
+    let accept = match accept {
+        Some(accept) => accept,
+        None => return false,
+    };
+
+    let mut wildcard = None;
+    for item in accept.0 {
+        match item.item {
+            ref enc if enc == encoding => return item.quality > q(0),
+            Encoding::EncodingExt(ref enc) if enc == "*" => {
+                wildcard = Some(item.quality)
+            }
+            _ => (),
+        }
+    }
+    wildcard.map(|quality| quality > q(0)).unwrap_or(false)
+}
+
+/// Stream to read request line by line.
+pub struct Readlines<T: HttpMessage> {
+    stream: Payload<T::Stream>,
+    buff: BytesMut,
+    limit: usize,
+    checked_buff: bool,
+    trim: bool,
+    trailing_newline: bool,
+    encoding: EncodingRef,
+    err: Option<ReadlinesError>,
+}
+
+impl<T> Readlines<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create a new stream to read request line by line.
+    fn new(req: &mut T) -> Self {
+        let encoding = match req.encoding() {
+            Ok(enc) => enc,
+            Err(err) => return Self::err(err.into()),
+        };
+
+        Readlines {
+            stream: req.take_payload(),
+            buff: BytesMut::with_capacity(262_144),
+            limit: 262_144,
+            checked_buff: true,
+            trim: false,
+            trailing_newline: false,
+            err: None,
+            encoding,
+        }
+    }
+
+    /// Change max line size. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Strip leading and trailing ASCII whitespace, including line
+    /// terminator, from each line. By default lines are returned as is.
+    pub fn trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Treat final line without terminator as `ReadlinesError::Incomplete`
+    /// error. By default such line is yielded.
+    pub fn require_trailing_newline(mut self, require: bool) -> Self {
+        self.trailing_newline = require;
+        self
+    }
+
+    /// Seed the stream with bytes already read from the payload.
+    ///
+    /// Seeded bytes are processed before the rest of the payload.
+    pub fn with_prefix(mut self, prefix: BytesMut) -> Self {
+        let mut buff = prefix;
+        buff.extend_from_slice(&self.buff);
+        self.buff = buff;
+        self.checked_buff = false;
+        self
+    }
+
+    fn output(&self, line: String) -> String {
+        if self.trim {
+            line.trim_matches(|c: char| c.is_ascii_whitespace())
+                .to_owned()
+        } else {
+            line
+        }
+    }
+
+    fn err(err: ReadlinesError) -> Self {
+        Readlines {
+            stream: Payload::None,
+            buff: BytesMut::new(),
+            limit: 262_144,
+            checked_buff: true,
+            trim: false,
+            trailing_newline: false,
+            encoding: UTF_8,
+            err: Some(err),
+        }
+    }
+}
+
+impl<T> Stream for Readlines<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = String;
+    type Error = ReadlinesError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if let Some(err) = self.err.take() {
+            return Err(err);
+        }
+
+        // check if there is a newline in the buffer
+        if !self.checked_buff {
+            let mut found: Option<usize> = None;
+            for (ind, b) in self.buff.iter().enumerate() {
+                if *b == b'\n' {
+                    found = Some(ind);
+                    break;
+                }
+            }
+            if let Some(ind) = found {
+                // check if line is longer than limit
+                if ind + 1 > self.limit {
+                    return Err(ReadlinesError::LimitOverflow);
+                }
+                let enc: *const Encoding = self.encoding as *const Encoding;
+                let line = if enc == UTF_8 {
+                    str::from_utf8(&self.buff.split_to(ind + 1))
+                        .map_err(|_| ReadlinesError::EncodingError)?
+                        .to_owned()
+                } else {
+                    self.encoding
+                        .decode(&self.buff.split_to(ind + 1), DecoderTrap::Strict)
+                        .map_err(|_| ReadlinesError::EncodingError)?
+                };
+                return Ok(Async::Ready(Some(self.output(line))));
+            }
+            self.checked_buff = true;
+        }
+        // poll req for more bytes, until a line is complete
+        loop {
+            match self.stream.poll() {
+                Ok(Async::Ready(Some(mut bytes))) => {
+                    // check if there is a newline in bytes
+                    let mut found: Option<usize> = None;
+                    for (ind, b) in bytes.iter().enumerate() {
+                        if *b == b'\n' {
+                            found = Some(ind);
+                            break;
+                        }
+                    }
+                    if let Some(ind) = found {
+                        // line starts with buffered bytes
+                        let mut buf = self.buff.take();
+                        buf.extend_from_slice(&bytes.split_to(ind + 1));
+                        // check if line is longer than limit
+                        if buf.len() > self.limit {
+                            return Err(ReadlinesError::LimitOverflow);
+                        }
+                        let enc: *const Encoding = self.encoding as *const Encoding;
+                        let line = if enc == UTF_8 {
+                            str::from_utf8(&buf)
+                                .map_err(|_| ReadlinesError::EncodingError)?
+                                .to_owned()
+                        } else {
+                            self.encoding
+                                .decode(&buf, DecoderTrap::Strict)
+                                .map_err(|_| ReadlinesError::EncodingError)?
+                        };
+                        // extend buffer with rest of the bytes;
+                        self.buff.extend_from_slice(&bytes);
+                        self.checked_buff = false;
+                        return Ok(Async::Ready(Some(self.output(line))));
+                    }
+                    self.buff.extend_from_slice(&bytes);
+                    // line can not fit into the limit anymore
+                    if self.buff.len() > self.limit {
+                        return Err(ReadlinesError::LimitOverflow);
+                    }
+                }
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Ok(Async::Ready(None)) => {
+                    if self.buff.is_empty() {
+                        return Ok(Async::Ready(None));
+                    }
+                    if self.trailing_newline {
+                        return Err(ReadlinesError::Incomplete);
+                    }
+                    if self.buff.len() > self.limit {
+                        return Err(ReadlinesError::LimitOverflow);
+                    }
+                    let enc: *const Encoding = self.encoding as *const Encoding;
+                    let line = if enc == UTF_8 {
+                        str::from_utf8(&self.buff)
+                            .map_err(|_| ReadlinesError::EncodingError)?
+                            .to_owned()
+                    } else {
+                        self.encoding
+                            .decode(&self.buff, DecoderTrap::Strict)
+                            .map_err(|_| ReadlinesError::EncodingError)?
+                    };
+                    self.buff.clear();
+                    return Ok(Async::Ready(Some(self.output(line))));
+                }
+                Err(e) => return Err(ReadlinesError::from(e)),
+            }
+        }
+    }
+}
+
+/// Server-sent event, see `HttpMessage::sse_events()`
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SseEvent {
+    /// Event type, `None` means default *message* type
+    pub event: Option<String>,
+    /// Event data, `data` lines joined with `\n`
+    pub data: String,
+    /// Last event id, it persists across events until changed
+    pub id: Option<String>,
+    /// Reconnection time in milliseconds, it persists across events
+    /// until changed
+    pub retry: Option<u64>,
+}
+
+/// Stream of server-sent events.
+///
+/// Lines must be terminated by LF or CRLF, lone CR line endings are not
+/// supported.
+pub struct SseEvents<T: HttpMessage> {
+    lines: Readlines<T>,
+    limit: usize,
+    event: Option<String>,
+    data: String,
+    has_data: bool,
+    last_id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl<T> SseEvents<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create a new stream of server-sent events.
+    pub fn new(req: &mut T) -> Self {
+        SseEvents {
+            lines: Readlines::new(req),
+            limit: 262_144,
+            event: None,
+            data: String::new(),
+            has_data: false,
+            last_id: None,
+            retry: None,
+        }
+    }
+
+    /// Change max line and event size. By default max size is 256Kb
+    ///
+    /// Event size is size of its type and data, larger event causes
+    /// `ReadlinesError::LimitOverflow` error.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.lines = self.lines.limit(limit);
+        self.limit = limit;
+        self
+    }
+
+    /// Process single line, returns completed event on blank line.
+    fn process(&mut self, line: &str) -> Result<Option<SseEvent>, ReadlinesError> {
+        if line.is_empty() {
+            // events without data are not dispatched
+            let has_data = self.has_data;
+            self.has_data = false;
+            let event = self.event.take();
+            let mut data = std::mem::replace(&mut self.data, String::new());
+            if !has_data {
+                return Ok(None);
+            }
+            data.pop();
+            return Ok(Some(SseEvent {
+                event,
+                data,
+                id: self.last_id.clone(),
+                retry: self.retry,
+            }));
+        }
+        if line.starts_with(':') {
+            return Ok(None);
+        }
+
+        let (field, value) = match line.find(':') {
+            Some(idx) => {
+                // single leading space is not part of the value
+                let value = &line[idx + 1..];
+                if value.starts_with(' ') {
+                    (&line[..idx], &value[1..])
+                } else {
+                    (&line[..idx], value)
+                }
+            }
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_owned()),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+                self.has_data = true;
+            }
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_owned()),
+            "retry" => {
+                if let Ok(retry) = value.parse() {
+                    self.retry = Some(retry);
+                }
+            }
+            _ => (),
+        }
+
+        let size = self.data.len() + self.event.as_ref().map(|e| e.len()).unwrap_or(0);
+        if size > self.limit {
+            return Err(ReadlinesError::LimitOverflow);
+        }
+        Ok(None)
+    }
+}
+
+impl<T> Stream for SseEvents<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = SseEvent;
+    type Error = ReadlinesError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            match self.lines.poll()? {
+                Async::Ready(Some(line)) => {
+                    let line = line.trim_end_matches('\n').trim_end_matches('\r');
+                    if let Some(event) = self.process(line)? {
+                        return Ok(Async::Ready(Some(event)));
+                    }
+                }
+                // incomplete trailing event is discarded
+                Async::Ready(None) => return Ok(Async::Ready(None)),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// `AsyncRead` reader of the message body.
+pub struct BodyReader<T: HttpMessage> {
+    stream: Payload<T::Stream>,
+    buf: Bytes,
+    limit: usize,
+    size: usize,
+}
+
+impl<T> BodyReader<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create a new reader of the body.
+    pub fn new(req: &mut T) -> Self {
+        BodyReader {
+            stream: req.take_payload(),
+            buf: Bytes::new(),
+            limit: 262_144,
+            size: 0,
+        }
+    }
+
+    /// Change max size of payload. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+impl<T> io::Read for BodyReader<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if !self.buf.is_empty() {
+                let n = std::cmp::min(buf.len(), self.buf.len());
+                buf[..n].copy_from_slice(&self.buf.split_to(n));
+                return Ok(n);
+            }
+
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => {
+                    self.size += chunk.len();
+                    if self.size > self.limit {
+                        return Err(PayloadError::Overflow.into());
+                    }
+                    self.buf = chunk;
+                }
+                Async::Ready(None) => return Ok(0),
+                Async::NotReady => return Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+}
+
+impl<T> AsyncRead for BodyReader<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+}
+
+/// Stream of utf-8 validated body chunks.
+pub struct Utf8Chunks<T: HttpMessage> {
+    stream: Payload<T::Stream>,
+    buf: BytesMut,
+}
+
+impl<T> Utf8Chunks<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create a new stream of utf-8 validated body chunks.
+    pub fn new(req: &mut T) -> Self {
+        Utf8Chunks {
+            stream: req.take_payload(),
+            buf: BytesMut::new(),
+        }
+    }
+}
+
+impl<T> Stream for Utf8Chunks<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = String;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => self.buf.extend_from_slice(&chunk),
+                Async::Ready(None) => {
+                    if self.buf.is_empty() {
+                        return Ok(Async::Ready(None));
+                    }
+                    // incomplete sequence at the end of payload
+                    let buf = self.buf.take();
+                    return match str::from_utf8(&buf) {
+                        Ok(s) => Ok(Async::Ready(Some(s.to_owned()))),
+                        Err(e) => Err(PayloadError::Utf8(e)),
+                    };
+                }
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+
+            let valid = match str::from_utf8(&self.buf) {
+                Ok(_) => self.buf.len(),
+                Err(e) => {
+                    if e.error_len().is_some() {
+                        return Err(PayloadError::Utf8(e));
+                    }
+                    e.valid_up_to()
+                }
+            };
+            if valid > 0 {
+                let chunk = self.buf.split_to(valid);
+                let s = str::from_utf8(&chunk).map_err(PayloadError::Utf8)?;
+                return Ok(Async::Ready(Some(s.to_owned())));
+            }
+        }
+    }
+}
+
+/// Encoding of the frame length prefix
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LengthPrefix {
+    /// Big-endian `u16`
+    U16Be,
+    /// Little-endian `u16`
+    U16Le,
+    /// Big-endian `u32`
+    U32Be,
+    /// Little-endian `u32`
+    U32Le,
+}
+
+impl LengthPrefix {
+    fn size(self) -> usize {
+        match self {
+            LengthPrefix::U16Be | LengthPrefix::U16Le => 2,
+            LengthPrefix::U32Be | LengthPrefix::U32Le => 4,
+        }
+    }
+
+    fn read(self, buf: &[u8]) -> usize {
+        match self {
+            LengthPrefix::U16Be => BigEndian::read_u16(buf) as usize,
+            LengthPrefix::U16Le => LittleEndian::read_u16(buf) as usize,
+            LengthPrefix::U32Be => BigEndian::read_u32(buf) as usize,
+            LengthPrefix::U32Le => LittleEndian::read_u32(buf) as usize,
+        }
+    }
+}
+
+/// Stream to read length prefixed binary frames.
+///
+/// Frames may span multiple payload chunks. Returns error:
+///
+/// * frame is larger than frame limit, 256Kb by default
+/// * total payload size exceeds the limit, if it is set
+/// * payload ends in the middle of the frame
+pub struct LengthPrefixedFrames<T: HttpMessage> {
+    stream: Payload<T::Stream>,
+    prefix: LengthPrefix,
+    buf: BytesMut,
+    frame_limit: usize,
+    limit: Option<usize>,
+    size: usize,
+}
+
+impl<T> LengthPrefixedFrames<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create a new stream to read length prefixed frames.
+    pub fn new(req: &mut T, prefix: LengthPrefix) -> Self {
+        LengthPrefixedFrames {
+            prefix,
+            stream: req.take_payload(),
+            buf: BytesMut::new(),
+            frame_limit: 262_144,
+            limit: None,
+            size: 0,
+        }
+    }
+
+    /// Change max size of a single frame. By default max size is 256Kb
+    pub fn frame_limit(mut self, limit: usize) -> Self {
+        self.frame_limit = limit;
+        self
+    }
+
+    /// Set max size of the whole payload. By default it is not limited.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Bytes>, PayloadError> {
+        let size = self.prefix.size();
+        if self.buf.len() < size {
+            return Ok(None);
+        }
+        let len = self.prefix.read(&self.buf[..size]);
+        if len > self.frame_limit {
+            return Err(PayloadError::Overflow);
+        }
+        if self.buf.len() < size + len {
+            return Ok(None);
+        }
+        self.buf.split_to(size);
+        Ok(Some(self.buf.split_to(len).freeze()))
+    }
+}
+
+impl<T> Stream for LengthPrefixedFrames<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(frame) = self.next_frame()? {
+                return Ok(Async::Ready(Some(frame)));
+            }
+
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => {
+                    self.size += chunk.len();
+                    if self.limit.map(|limit| self.size > limit).unwrap_or(false) {
+                        return Err(PayloadError::Overflow);
+                    }
+                    self.buf.extend_from_slice(&chunk);
+                }
+                Async::Ready(None) => {
+                    return if self.buf.is_empty() {
+                        Ok(Async::Ready(None))
+                    } else {
+                        Err(PayloadError::Incomplete(None))
+                    };
+                }
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// Stream of payload records separated by a multi-byte delimiter.
+///
+/// Delimiter may straddle chunk boundaries. Trailing data without
+/// the delimiter is yielded as the last record. Both record and total
+/// sizes are limited, overflow is reported as `PayloadError::Overflow`.
+pub struct SplitBody<T: HttpMessage> {
+    stream: Payload<T::Stream>,
+    delimiter: Bytes,
+    buf: BytesMut,
+    checked: usize,
+    record_limit: usize,
+    limit: usize,
+    size: usize,
+    eof: bool,
+}
+
+impl<T> SplitBody<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create a new stream to read delimited records.
+    ///
+    /// Panics if `delimiter` is empty.
+    pub fn new(req: &mut T, delimiter: &[u8], limit: usize) -> Self {
+        assert!(!delimiter.is_empty(), "delimiter must not be empty");
+        SplitBody {
+            limit,
+            stream: req.take_payload(),
+            delimiter: Bytes::from(delimiter),
+            buf: BytesMut::new(),
+            checked: 0,
+            record_limit: limit,
+            size: 0,
+            eof: false,
+        }
+    }
+
+    /// Change max size of a single record, delimiter excluded.
+    /// By default it equals to the total limit.
+    pub fn record_limit(mut self, limit: usize) -> Self {
+        self.record_limit = limit;
+        self
+    }
+
+    fn next_record(&mut self) -> Result<Option<Bytes>, PayloadError> {
+        let found = find_delimiter(
+            &self.buf,
+            &self.delimiter,
+            &mut self.checked,
+            self.record_limit,
+        )?;
+        if let Some(end) = found {
+            let record = self.buf.split_to(end).freeze();
+            self.buf.split_to(self.delimiter.len());
+            return Ok(Some(record));
+        }
+        Ok(None)
+    }
+}
+
+impl<T> Stream for SplitBody<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if let Some(record) = self.next_record()? {
+                return Ok(Async::Ready(Some(record)));
+            }
+            if self.eof {
+                return if self.buf.is_empty() {
+                    Ok(Async::Ready(None))
+                } else if self.buf.len() > self.record_limit {
+                    Err(PayloadError::Overflow)
+                } else {
+                    self.checked = 0;
+                    Ok(Async::Ready(Some(self.buf.take().freeze())))
+                };
+            }
+
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => {
+                    self.size += chunk.len();
+                    if self.size > self.limit {
+                        return Err(PayloadError::Overflow);
+                    }
+                    self.buf.extend_from_slice(&chunk);
+                }
+                Async::Ready(None) => self.eof = true,
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// Future that reads payload up to the first occurrence of a marker.
+///
+/// Marker may straddle chunk boundaries. Bytes preceding the marker are
+/// limited to `limit`, otherwise `PayloadError::Overflow` get returned.
+/// If payload ends before the marker, `PayloadError::Incomplete` get
+/// returned.
+pub struct BodyUntil<T: HttpMessage> {
+    stream: Option<Payload<T::Stream>>,
+    marker: Bytes,
+    buf: BytesMut,
+    checked: usize,
+    limit: usize,
+}
+
+impl<T> BodyUntil<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+{
+    /// Create a new future to read payload up to the marker.
+    ///
+    /// Panics if `marker` is empty.
+    pub fn new(req: &mut T, marker: &[u8], limit: usize) -> Self {
+        assert!(!marker.is_empty(), "marker must not be empty");
+        BodyUntil {
+            limit,
+            stream: Some(req.take_payload()),
+            marker: Bytes::from(marker),
+            buf: BytesMut::new(),
+            checked: 0,
+        }
+    }
+}
+
+impl<T> Future for BodyUntil<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+{
+    type Item = (Bytes, Payload);
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let found =
+                find_delimiter(&self.buf, &self.marker, &mut self.checked, self.limit)?;
+            if let Some(end) = found {
+                let before = self.buf.split_to(end).freeze();
+                self.buf.split_to(self.marker.len());
+                let rest = self.buf.take().freeze();
+                let stream = self.stream.take().expect("Use after resolve");
+                let after: PayloadStream = if rest.is_empty() {
+                    Box::new(stream)
+                } else {
+                    Box::new(futures::stream::once(Ok(rest)).chain(stream))
+                };
+                return Ok(Async::Ready((before, Payload::Stream(after))));
+            }
+
+            match self.stream.as_mut().expect("Use after resolve").poll()? {
+                Async::Ready(Some(chunk)) => self.buf.extend_from_slice(&chunk),
+                Async::Ready(None) => return Err(PayloadError::Incomplete(None)),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// Stream of deserialized csv records.
+///
+/// Records may span multiple payload chunks, quoted fields may contain
+/// line breaks. Returns error:
+///
+/// * record is larger than record limit, 256Kb by default
+/// * total payload size exceeds the limit, 256Kb by default
+/// * record can not be deserialized
+pub struct CsvStream<T: HttpMessage, U> {
+    stream: Payload<T::Stream>,
+    reader: CsvReader,
+    buf: BytesMut,
+    output: Vec<u8>,
+    output_len: usize,
+    ends: Vec<usize>,
+    ends_len: usize,
+    has_headers: bool,
+    headers: Option<ByteRecord>,
+    record_limit: usize,
+    record_size: usize,
+    limit: usize,
+    size: usize,
+    eof: bool,
+    _t: PhantomData<U>,
+}
+
+impl<T, U> CsvStream<T, U>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    U: DeserializeOwned,
+{
+    /// Create a new stream to read csv records.
+    pub fn new(req: &mut T) -> Self {
+        CsvStream {
+            stream: req.take_payload(),
+            reader: CsvReader::new(),
+            buf: BytesMut::new(),
+            output: vec![0; 1024],
+            output_len: 0,
+            ends: vec![0; 16],
+            ends_len: 0,
+            has_headers: true,
+            headers: None,
+            record_limit: 262_144,
+            record_size: 0,
+            limit: 262_144,
+            size: 0,
+            eof: false,
+            _t: PhantomData,
+        }
+    }
+
+    /// Treat first record as a header row. By default it is enabled.
+    ///
+    /// Without header row records can be deserialized only to
+    /// sequence-like types, i.e. tuples.
+    pub fn has_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+
+    /// Change max size of a single record. By default max size is 256Kb
+    ///
+    /// Size of the raw record, including delimiters and quotes, is checked.
+    pub fn record_limit(mut self, limit: usize) -> Self {
+        self.record_limit = limit;
+        self
+    }
+
+    /// Change max size of the whole payload. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    fn take_record(&mut self) -> ByteRecord {
+        let mut record = ByteRecord::with_capacity(self.output_len, self.ends_len);
+        let mut start = 0;
+        for end in &self.ends[..self.ends_len] {
+            record.push_field(&self.output[start..*end]);
+            start = *end;
+        }
+        self.output_len = 0;
+        self.ends_len = 0;
+        record
+    }
+}
+
+impl<T, U> Stream for CsvStream<T, U>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    U: DeserializeOwned,
+{
+    type Item = U;
+    type Error = CsvError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if !self.buf.is_empty() || self.eof {
+                let (res, nin, nout, nends) = self.reader.read_record(
+                    &self.buf,
+                    &mut self.output[self.output_len..],
+                    &mut self.ends[self.ends_len..],
+                );
+                self.buf.split_to(nin);
+                self.output_len += nout;
+                self.ends_len += nends;
+                self.record_size += nin;
+                if self.record_size > self.record_limit {
+                    return Err(CsvError::Overflow);
+                }
+
+                match res {
+                    ReadRecordResult::InputEmpty => (),
+                    ReadRecordResult::OutputFull => {
+                        let len = self.output.len() * 2;
+                        self.output.resize(len, 0);
+                        continue;
+                    }
+                    ReadRecordResult::OutputEndsFull => {
+                        let len = self.ends.len() * 2;
+                        self.ends.resize(len, 0);
+                        continue;
+                    }
+                    ReadRecordResult::Record => {
+                        self.record_size = 0;
+                        let record = self.take_record();
+                        if self.has_headers && self.headers.is_none() {
+                            self.headers = Some(record);
+                            continue;
+                        }
+                        let item = record.deserialize(self.headers.as_ref())?;
+                        return Ok(Async::Ready(Some(item)));
+                    }
+                    ReadRecordResult::End => return Ok(Async::Ready(None)),
+                }
+            }
+
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => {
+                    self.size += chunk.len();
+                    if self.size > self.limit {
+                        return Err(CsvError::Overflow);
+                    }
+                    self.buf.extend_from_slice(&chunk);
+                }
+                Async::Ready(None) => self.eof = true,
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// Future that resolves to a complete http message body.
+///
+/// Dropping the future releases the underlying payload, whether collection
+/// has started or not. Use `abort()` to release it while keeping the future.
+pub struct MessageBody<T: HttpMessage> {
+    limit: usize,
+    limit_fn: Option<Box<FnOnce() -> usize>>,
+    min_length: usize,
+    coalesce: usize,
+    length: Option<usize>,
+    stream: Payload<T::Stream>,
+    content_type: Option<HeaderValue>,
+    encoding: Option<ContentEncoding>,
+    decompress: bool,
+    max_ratio: f64,
+    min_rate: usize,
+    inactivity: Option<Duration>,
+    on_first_byte: Option<Box<FnOnce(Duration)>>,
+    on_percent: Option<Box<FnMut(u8)>>,
+    on_stall: Option<(Duration, Box<FnMut()>)>,
+    transform:
+        Option<Box<FnMut(Bytes) -> Box<Future<Item = Bytes, Error = PayloadError>>>>,
+    err: Option<PayloadError>,
+    fut: Option<Box<Future<Item = Bytes, Error = PayloadError>>>,
+}
+
+impl<T> MessageBody<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create `MessageBody` for request.
+    pub fn new(req: &mut T) -> MessageBody<T> {
+        let len = match parse_content_length(req.headers()) {
+            Ok(len) => len,
+            Err(e) => return Self::err(e),
+        };
+
+        let encoding = content_encoding(req.headers());
+
+        MessageBody {
+            stream: req.take_payload(),
+            content_type: req.headers().get(header::CONTENT_TYPE).cloned(),
+            limit: 262_144,
+            limit_fn: None,
+            min_length: 0,
+            coalesce: 0,
+            length: len,
+            encoding,
+            decompress: false,
+            max_ratio: 100.0,
+            min_rate: 0,
+            inactivity: None,
+            on_first_byte: None,
+            on_percent: None,
+            on_stall: None,
+            transform: None,
+            fut: None,
+            err: None,
+        }
+    }
+
+    /// Change max size of payload. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Compute max size of payload with `f` when the future is first polled.
+    ///
+    /// Computed limit replaces the current one, i.e. `f` can look up
+    /// per-tenant quota resolved after the future is created.
+    pub fn limit_fn<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce() -> usize + 'static,
+    {
+        self.limit_fn = Some(Box::new(f));
+        self
+    }
+
+    /// Change min size of payload. By default there is no min size.
+    ///
+    /// Shorter payload resolves to `PayloadError::Incomplete` error once
+    /// the stream ends.
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    /// Buffer chunks smaller than `min_chunk` and append them to the body
+    /// in batches.
+    ///
+    /// Reduces number of reallocations for payloads delivered in many tiny
+    /// chunks, resulting body is the same. Disabled by default.
+    pub fn coalesce(mut self, min_chunk: usize) -> Self {
+        self.coalesce = min_chunk;
+        self
+    }
+
+    /// Change max size of payload depending on request's content type.
+    ///
+    /// `f` receives value of `HttpMessage::content_type()`, returned zero
+    /// resets limit to default 256Kb.
+    pub fn limit_by_content_type<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> usize,
+    {
+        let content_type = self
+            .content_type
+            .as_ref()
+            .and_then(|ct| ct.to_str().ok())
+            .map(|ct| ct.split(';').next().unwrap().trim())
+            .unwrap_or("");
+        self.limit = match f(content_type) {
+            0 => 262_144,
+            limit => limit,
+        };
+        self
+    }
+
+    /// Decompress payload according to *Content-Encoding* header.
+    ///
+    /// Size limit is applied to decompressed body. Future fails with
+    /// `PayloadError::EncodingCorrupted` if content coding is not supported.
+    pub fn decompress(mut self) -> Self {
+        self.decompress = true;
+        self
+    }
+
+    /// Set max allowed ratio between decompressed and compressed body sizes.
+    ///
+    /// Decompression fails with `PayloadError::Overflow` as soon as output
+    /// exceeds `ratio * compressed input`. By default ratio is 100.
+    pub fn max_decompress_ratio(mut self, ratio: f64) -> Self {
+        self.max_ratio = ratio;
+        self
+    }
+
+    /// Require payload to be received at least `bytes_per_sec` rate.
+    ///
+    /// Slow payload fails with `PayloadError::TooSlow`, see `MinRate`.
+    /// Check is disabled by default.
+    pub fn min_rate(mut self, bytes_per_sec: usize) -> Self {
+        self.min_rate = bytes_per_sec;
+        self
+    }
+
+    /// Fail with `PayloadError::Timeout` if no data is received
+    /// within `timeout` period, see `InactivityTimeout`.
+    pub fn inactivity_timeout(mut self, timeout: Duration) -> Self {
+        self.inactivity = Some(timeout);
+        self
+    }
+
+    /// Call `f` each time no data is received within `period`, see
+    /// `OnStall`.
+    ///
+    /// Unlike `inactivity_timeout()` collection is not aborted, `f` is
+    /// called again after every further `period` without data.
+    pub fn on_stall<F>(mut self, period: Duration, f: F) -> Self
+    where
+        F: FnMut() + 'static,
+    {
+        self.on_stall = Some((period, Box::new(f)));
+        self
+    }
+
+    /// Call `f` with time elapsed between start of collection
+    /// and arrival of the first chunk.
+    ///
+    /// Callback is called at most once, it is not called for empty payload.
+    pub fn on_first_byte<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(Duration) + 'static,
+    {
+        self.on_first_byte = Some(Box::new(f));
+        self
+    }
+
+    /// Map error of the future with `f`.
+    pub fn map_err_with<E, F>(self, f: F) -> MapErr<Self, F>
+    where
+        T::Stream: 'static,
+        F: FnOnce(PayloadError) -> E,
+    {
+        self.map_err(f)
+    }
+
+    /// Convert error of the future to `io::Error`.
+    ///
+    /// See `Payload::into_io()` for errors mapping.
+    pub fn into_io(self) -> FromErr<Self, io::Error>
+    where
+        T::Stream: 'static,
+    {
+        self.from_err()
+    }
+
+    /// Call `f` with percentage (0-100) of received payload as chunks
+    /// arrive.
+    ///
+    /// Percentage is computed against *Content-Length* header, callback is
+    /// not called if payload length is unknown.
+    pub fn on_percent<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(u8) + 'static,
+    {
+        self.on_percent = Some(Box::new(f));
+        self
+    }
+
+    /// Transform each chunk with async function `f` before it is collected.
+    ///
+    /// Chunks are transformed after decompression, size limit is applied
+    /// to transformed body. Error returned by `f` aborts collection.
+    pub fn transform<F, Fut>(mut self, mut f: F) -> Self
+    where
+        F: FnMut(Bytes) -> Fut + 'static,
+        Fut: Future<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        self.transform = Some(Box::new(move |chunk| Box::new(f(chunk))));
+        self
+    }
+
+    /// Drop underlying payload immediately.
+    ///
+    /// Future resolves to `PayloadError::Incomplete` error after abort.
+    pub fn abort(&mut self) {
+        self.stream = Payload::None;
+        self.fut = None;
+        self.err = Some(PayloadError::Incomplete(None));
+    }
+
+    fn err(e: PayloadError) -> Self {
+        MessageBody {
+            stream: Payload::None,
+            content_type: None,
+            limit: 262_144,
+            limit_fn: None,
+            min_length: 0,
+            coalesce: 0,
+            encoding: Some(ContentEncoding::Identity),
+            decompress: false,
+            max_ratio: 100.0,
+            min_rate: 0,
+            inactivity: None,
+            on_first_byte: None,
+            on_percent: None,
+            on_stall: None,
+            transform: None,
+            fut: None,
+            err: Some(e),
+            length: None,
+        }
+    }
+}
+
+impl<T> Future for MessageBody<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(ref mut fut) = self.fut {
+            return fut.poll();
+        }
+
+        if let Some(err) = self.err.take() {
+            return Err(err);
+        }
+
+        if let Some(f) = self.limit_fn.take() {
+            self.limit = f();
+        }
+        let length = self.length.take();
+        if let Some(len) = length {
+            if len > self.limit {
+                return Err(PayloadError::Overflow);
+            }
+        }
+
+        let mut stream: PayloadStream =
+            Box::new(std::mem::replace(&mut self.stream, Payload::None));
+        if let (Some(mut f), Some(total)) = (self.on_percent.take(), length) {
+            let mut received = 0;
+            stream = Box::new(stream.map(move |chunk| {
+                received += chunk.len();
+                let percent = if total == 0 {
+                    100
+                } else {
+                    (received as u64 * 100 / total as u64).min(100)
+                };
+                f(percent as u8);
+                chunk
+            }));
+        }
+        if self.min_rate > 0 {
+            stream = Box::new(MinRate::new(stream, self.min_rate));
+        }
+        if let Some(timeout) = self.inactivity {
+            stream = Box::new(InactivityTimeout::new(stream, timeout));
+        }
+        if let Some((period, f)) = self.on_stall.take() {
+            stream = Box::new(OnStall::new(stream, period, f));
+        }
+        if self.decompress {
+            let encoding = match self.encoding {
+                Some(encoding) => encoding,
+                None => return Err(PayloadError::EncodingCorrupted),
+            };
+            stream = Box::new(
+                Decoder::new(stream, encoding)
+                    .max_ratio(self.max_ratio)
+                    .limit(self.limit),
+            );
+        }
+        if let Some(mut f) = self.transform.take() {
+            stream = Box::new(stream.and_then(move |chunk| f(chunk)));
+        }
+
+        // future
+        let limit = self.limit;
+        let min_length = self.min_length;
+        let start = Instant::now();
+        let mut on_first_byte = self.on_first_byte.take();
+        let coalesce = self.coalesce;
+        self.fut = Some(Box::new(
+            stream
+                .fold(
+                    (BytesMut::with_capacity(8192), BytesMut::new()),
+                    move |(mut body, mut pending), chunk| {
+                        if let Some(f) = on_first_byte.take() {
+                            f(start.elapsed());
+                        }
+                        if (body.len() + pending.len() + chunk.len()) > limit {
+                            return Err(PayloadError::Overflow);
+                        }
+                        if chunk.len() >= coalesce {
+                            if !pending.is_empty() {
+                                body.extend_from_slice(&pending);
+                                pending.clear();
+                            }
+                            body.extend_from_slice(&chunk);
+                        } else {
+                            // small chunks are collected in scratch buffer,
+                            // its allocation is reused between batches
+                            if pending.capacity() < coalesce {
+                                pending.reserve(coalesce);
+                            }
+                            pending.extend_from_slice(&chunk);
+                            if pending.len() >= coalesce {
+                                body.extend_from_slice(&pending);
+                                pending.clear();
+                            }
+                        }
+                        Ok((body, pending))
+                    },
+                )
+                .and_then(move |(mut body, pending)| {
+                    body.extend_from_slice(&pending);
+                    if body.len() < min_length {
+                        Err(PayloadError::Incomplete(None))
+                    } else {
+                        Ok(body.freeze())
+                    }
+                }),
+        ));
+        self.poll()
+    }
+}
+
+/// Future that resolves to a list of received message body chunks.
+pub struct BodySegments<T: HttpMessage> {
+    limit: usize,
+    length: Option<usize>,
+    stream: Payload<T::Stream>,
+    segments: Vec<Bytes>,
+    size: usize,
+    err: Option<PayloadError>,
+}
+
+impl<T> BodySegments<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create `BodySegments` for request.
+    pub fn new(req: &mut T) -> BodySegments<T> {
+        let (length, err) = match parse_content_length(req.headers()) {
+            Ok(len) => (len, None),
+            Err(e) => (None, Some(e)),
+        };
+        let stream = if err.is_none() {
+            req.take_payload()
+        } else {
+            Payload::None
+        };
+
+        BodySegments {
+            stream,
+            length,
+            err,
+            limit: 262_144,
+            segments: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// Change max total size of payload. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+impl<T> Future for BodySegments<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Vec<Bytes>;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(err) = self.err.take() {
+            return Err(err);
+        }
+        if let Some(len) = self.length.take() {
+            if len > self.limit {
+                return Err(PayloadError::Overflow);
+            }
+        }
+
+        loop {
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => {
+                    self.size += chunk.len();
+                    if self.size > self.limit {
+                        return Err(PayloadError::Overflow);
+                    }
+                    self.segments.push(chunk);
+                }
+                Async::Ready(None) => {
+                    return Ok(Async::Ready(std::mem::replace(
+                        &mut self.segments,
+                        Vec::new(),
+                    )));
+                }
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// Future that resolves to a parsed urlencoded values.
+pub struct UrlEncoded<T: HttpMessage, U> {
+    stream: Payload<T::Stream>,
+    limit: usize,
+    field_limit: Option<usize>,
+    length: Option<usize>,
+    encoding: EncodingRef,
+    nested: bool,
+    deadline: Option<Deadline>,
+    err: Option<UrlencodedError>,
+    fut: Option<Box<Future<Item = U, Error = UrlencodedError>>>,
+}
+
+impl<T, U> UrlEncoded<T, U>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create a new future to URL encode a request
+    pub fn new(req: &mut T) -> UrlEncoded<T, U> {
+        // check content type
+        let fut = Self::err(UrlencodedError::ContentType);
+        if req.content_type().to_lowercase() != "application/x-www-form-urlencoded" {
+            fut
+        } else {
+            fut.read_from(req)
+        }
+    }
+
+    fn read_from(mut self, req: &mut T) -> Self {
+        self.encoding = match req.encoding() {
+            Ok(enc) => enc,
+            Err(_) => {
+                self.err = Some(UrlencodedError::ContentType);
+                return self;
+            }
+        };
+        self.length = match parse_content_length(req.headers()) {
+            Ok(len) => len,
+            Err(PayloadError::UnknownLength) => {
+                self.err = Some(UrlencodedError::UnknownLength);
+                return self;
+            }
+            Err(e) => {
+                self.err = Some(UrlencodedError::Payload(e));
+                return self;
+            }
+        };
+        self.stream = req.take_payload();
+        self.err = None;
+        self
+    }
+
+    fn err(e: UrlencodedError) -> Self {
+        UrlEncoded {
+            stream: Payload::None,
+            limit: 262_144,
+            field_limit: None,
+            nested: false,
+            deadline: None,
             fut: None,
+            err: Some(e),
+            length: None,
+            encoding: UTF_8,
+        }
+    }
+
+    /// Change max size of payload. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Set deadline for reading and parsing the body.
+    ///
+    /// Future fails with `UrlencodedError::Payload(PayloadError::Timeout)`
+    /// once deadline passes. By default there is no deadline.
+    pub fn deadline(mut self, at: Instant) -> Self {
+        self.deadline = Some(Deadline::new(at));
+        self
+    }
+
+    /// Parse nested, bracketed form structures like `user[name]=x&user[age]=3`.
+    ///
+    /// Nested forms are parsed with `serde_qs`, by default `serde_urlencoded`
+    /// is used.
+    pub fn nested(mut self, nested: bool) -> Self {
+        self.nested = nested;
+        self
+    }
+
+    /// Parse body regardless of request's content type.
+    ///
+    /// Content type mismatch leaves the payload in the message, so it is
+    /// taken from `req` here. `req` must be the message the future was
+    /// created for. Charset of the content type is still checked.
+    pub fn lenient_content_type(self, req: &mut T) -> Self {
+        match self.err {
+            Some(UrlencodedError::ContentType) => self.read_from(req),
+            _ => self,
+        }
+    }
+
+    /// Recover the payload taken from the message.
+    ///
+    /// Payload is available only before the future is polled. If the future
+    /// was created in error state (i.e. content type mismatch), payload was
+    /// not taken and remains in the message; `Payload::None` get returned.
+    pub fn into_payload(self) -> Payload<T::Stream> {
+        self.stream
+    }
+
+    /// Map error of the future with `f`.
+    pub fn map_err_with<E, F>(self, f: F) -> MapErr<Self, F>
+    where
+        T::Stream: 'static,
+        U: DeserializeOwned + 'static,
+        F: FnOnce(UrlencodedError) -> E,
+    {
+        self.map_err(f)
+    }
+
+    /// Set max size of a single `name=value` pair.
+    ///
+    /// Oversized field causes `UrlencodedError::FieldOverflow` error.
+    /// By default only total payload size is checked.
+    pub fn field_limit(mut self, limit: usize) -> Self {
+        self.field_limit = Some(limit);
+        self
+    }
+}
+
+impl<T, U> Future for UrlEncoded<T, U>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    U: DeserializeOwned + 'static,
+{
+    type Item = U;
+    type Error = UrlencodedError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(ref mut deadline) = self.deadline {
+            if deadline.expired() {
+                return Err(PayloadError::Timeout.into());
+            }
+        }
+
+        if let Some(ref mut fut) = self.fut {
+            return fut.poll();
+        }
+
+        if let Some(err) = self.err.take() {
+            return Err(err);
+        }
+
+        // payload size
+        let limit = self.limit;
+        if let Some(len) = self.length.take() {
+            if len > limit {
+                return Err(UrlencodedError::Overflow);
+            }
+        }
+
+        // future
+        let encoding = self.encoding;
+        let field_limit = self.field_limit;
+        let nested = self.nested;
+        let fut = std::mem::replace(&mut self.stream, Payload::None)
+            .from_err()
+            .fold(BytesMut::with_capacity(8192), move |mut body, chunk| {
+                if (body.len() + chunk.len()) > limit {
+                    let rest = limit - body.len();
+                    body.extend_from_slice(&chunk[..rest]);
+                    Err(UrlencodedError::OverflowWithData(body.freeze()))
+                } else {
+                    body.extend_from_slice(&chunk);
+                    Ok(body)
+                }
+            })
+            .and_then(move |body| {
+                if let Some(field_limit) = field_limit {
+                    if body.split(|b| *b == b'&').any(|f| f.len() > field_limit) {
+                        return Err(UrlencodedError::FieldOverflow);
+                    }
+                }
+
+                if nested {
+                    let body = if (encoding as *const Encoding) == UTF_8 {
+                        str::from_utf8(&body)
+                            .map_err(|_| UrlencodedError::Parse)?
+                            .to_owned()
+                    } else {
+                        encoding
+                            .decode(&body, DecoderTrap::Strict)
+                            .map_err(|_| UrlencodedError::Parse)?
+                    };
+                    serde_qs::from_str::<U>(&body).map_err(|_| UrlencodedError::Parse)
+                } else if (encoding as *const Encoding) == UTF_8 {
+                    serde_urlencoded::from_bytes::<U>(&body)
+                        .map_err(|_| UrlencodedError::Parse)
+                } else {
+                    let body = encoding
+                        .decode(&body, DecoderTrap::Strict)
+                        .map_err(|_| UrlencodedError::Parse)?;
+                    serde_urlencoded::from_str::<U>(&body)
+                        .map_err(|_| UrlencodedError::Parse)
+                }
+            });
+        self.fut = Some(Box::new(fut));
+        self.poll()
+    }
+}
+
+/// Future that resolves to a value of a single urlencoded form field.
+pub struct FormField<T: HttpMessage> {
+    stream: Payload<T::Stream>,
+    name: String,
+    limit: usize,
+    size: usize,
+    encoding: EncodingRef,
+    buf: BytesMut,
+    err: Option<UrlencodedError>,
+}
+
+impl<T> FormField<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create a new future to find a form field in the request's body
+    pub fn new(req: &mut T, name: &str) -> FormField<T> {
+        let mut fut = FormField {
+            stream: Payload::None,
+            name: name.to_owned(),
+            limit: 262_144,
+            size: 0,
+            encoding: UTF_8,
+            buf: BytesMut::new(),
             err: None,
+        };
+
+        // check content type
+        if req.content_type().to_lowercase() != "application/x-www-form-urlencoded" {
+            fut.err = Some(UrlencodedError::ContentType);
+            return fut;
+        }
+        match req.encoding() {
+            Ok(enc) => fut.encoding = enc,
+            Err(_) => {
+                fut.err = Some(UrlencodedError::ContentType);
+                return fut;
+            }
+        }
+        fut.stream = req.take_payload();
+        fut
+    }
+
+    /// Change max size of payload. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    fn find(&self, data: &[u8]) -> Result<Option<String>, UrlencodedError> {
+        let pairs = if (self.encoding as *const Encoding) == UTF_8 {
+            serde_urlencoded::from_bytes::<Vec<(String, String)>>(data)
+                .map_err(|_| UrlencodedError::Parse)?
+        } else {
+            let data = self
+                .encoding
+                .decode(data, DecoderTrap::Strict)
+                .map_err(|_| UrlencodedError::Parse)?;
+            serde_urlencoded::from_str::<Vec<(String, String)>>(&data)
+                .map_err(|_| UrlencodedError::Parse)?
+        };
+        Ok(pairs
+            .into_iter()
+            .find(|(key, _)| *key == self.name)
+            .map(|(_, value)| value))
+    }
+}
+
+impl<T> Future for FormField<T>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Option<String>;
+    type Error = UrlencodedError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(err) = self.err.take() {
+            return Err(err);
+        }
+
+        loop {
+            // check complete fields in the buffer
+            while let Some(pos) = self.buf.iter().position(|b| *b == b'&') {
+                let field = self.buf.split_to(pos + 1);
+                if let Some(value) = self.find(&field[..pos])? {
+                    return Ok(Async::Ready(Some(value)));
+                }
+            }
+
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => {
+                    self.size += chunk.len();
+                    if self.size > self.limit {
+                        return Err(UrlencodedError::Overflow);
+                    }
+                    self.buf.extend_from_slice(&chunk);
+                }
+                Async::Ready(None) => {
+                    let field = self.buf.take();
+                    return Ok(Async::Ready(self.find(&field)?));
+                }
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding::all::{ISO_8859_2, WINDOWS_1252};
+    use encoding::Encoding;
+    use futures::Async;
+    use mime;
+    use serde_derive::Deserialize;
+
+    use super::*;
+    use crate::test::TestRequest;
+
+    fn chunked_request(chunks: &[&'static [u8]]) -> crate::Request {
+        let chunks: Vec<_> = chunks.iter().map(|c| Bytes::from_static(*c)).collect();
+        let stream: PayloadStream =
+            Box::new(futures::stream::iter_ok::<_, PayloadError>(chunks));
+        crate::Request::with_payload(Payload::Stream(stream))
+    }
+
+    #[test]
+    fn test_content_type() {
+        let req = TestRequest::with_header("content-type", "text/plain").finish();
+        assert_eq!(req.content_type(), "text/plain");
+        let req =
+            TestRequest::with_header("content-type", "application/json; charset=utf=8")
+                .finish();
+        assert_eq!(req.content_type(), "application/json");
+        let req = TestRequest::default().finish();
+        assert_eq!(req.content_type(), "");
+    }
+
+    #[test]
+    fn test_content_type_top() {
+        let req = TestRequest::with_header("content-type", "image/png").finish();
+        assert_eq!(req.content_type_top(), Some("image"));
+        let req = TestRequest::with_header("content-type", "text/plain; charset=utf-8")
+            .finish();
+        assert_eq!(req.content_type_top(), Some("text"));
+        let req = TestRequest::with_header("content-type", "text").finish();
+        assert_eq!(req.content_type_top(), None);
+        let req = TestRequest::default().finish();
+        assert_eq!(req.content_type_top(), None);
+    }
+
+    #[test]
+    fn test_mime_type() {
+        let req = TestRequest::with_header("content-type", "application/json").finish();
+        assert_eq!(req.mime_type().unwrap(), Some(mime::APPLICATION_JSON));
+        let req = TestRequest::default().finish();
+        assert_eq!(req.mime_type().unwrap(), None);
+        let req =
+            TestRequest::with_header("content-type", "application/json; charset=utf-8")
+                .finish();
+        let mt = req.mime_type().unwrap().unwrap();
+        assert_eq!(mt.get_param(mime::CHARSET), Some(mime::UTF_8));
+        assert_eq!(mt.type_(), mime::APPLICATION);
+        assert_eq!(mt.subtype(), mime::JSON);
+    }
+
+    #[test]
+    fn test_mime_type_error() {
+        let req = TestRequest::with_header(
+            "content-type",
+            "applicationadfadsfasdflknadsfklnadsfjson",
+        )
+        .finish();
+        assert_eq!(Err(ContentTypeError::ParseError), req.mime_type());
+    }
+
+    #[test]
+    fn test_require_content_type() {
+        let req = TestRequest::with_header("content-type", "application/json").finish();
+        assert_eq!(req.require_content_type(), Ok(mime::APPLICATION_JSON));
+        let req = TestRequest::default().finish();
+        assert_eq!(req.require_content_type(), Err(ContentTypeError::Missing));
+        let req = TestRequest::with_header("content-type", "application").finish();
+        assert_eq!(
+            req.require_content_type(),
+            Err(ContentTypeError::ParseError)
+        );
+    }
+
+    #[test]
+    fn test_encoding() {
+        let req = TestRequest::default().finish();
+        assert_eq!(UTF_8.name(), req.encoding().unwrap().name());
+
+        let req = TestRequest::with_header("content-type", "application/json").finish();
+        assert_eq!(UTF_8.name(), req.encoding().unwrap().name());
+
+        let req = TestRequest::with_header(
+            "content-type",
+            "application/json; charset=ISO-8859-2",
+        )
+        .finish();
+        assert_eq!(ISO_8859_2.name(), req.encoding().unwrap().name());
+    }
+
+    #[test]
+    fn test_encoding_or() {
+        let req = TestRequest::default().finish();
+        assert_eq!(
+            WINDOWS_1252.name(),
+            req.encoding_or(WINDOWS_1252).unwrap().name()
+        );
+
+        let req = TestRequest::with_header("content-type", "text/plain").finish();
+        assert_eq!(
+            WINDOWS_1252.name(),
+            req.encoding_or(WINDOWS_1252).unwrap().name()
+        );
+
+        let req = TestRequest::with_header(
+            "content-type",
+            "application/json; charset=ISO-8859-2",
+        )
+        .finish();
+        assert_eq!(
+            ISO_8859_2.name(),
+            req.encoding_or(WINDOWS_1252).unwrap().name()
+        );
+    }
+
+    #[test]
+    fn test_encoding_error() {
+        let req = TestRequest::with_header("content-type", "applicatjson").finish();
+        assert_eq!(Some(ContentTypeError::ParseError), req.encoding().err());
+
+        let req = TestRequest::with_header(
+            "content-type",
+            "application/json; charset=kkkttktk",
+        )
+        .finish();
+        assert_eq!(
+            Some(ContentTypeError::UnknownEncoding),
+            req.encoding().err()
+        );
+    }
+
+    #[test]
+    fn test_accepts_encoding() {
+        let req = TestRequest::default().finish();
+        assert!(!req.accepts_gzip());
+
+        let req =
+            TestRequest::with_header(header::ACCEPT_ENCODING, "gzip, br;q=0.5").finish();
+        assert!(req.accepts_gzip());
+        assert!(req.accepts_brotli());
+        assert!(!req.accepts_deflate());
+
+        let req =
+            TestRequest::with_header(header::ACCEPT_ENCODING, "gzip;q=0, *").finish();
+        assert!(!req.accepts_gzip());
+        assert!(req.accepts_brotli());
+
+        let req =
+            TestRequest::with_header(header::ACCEPT_ENCODING, "deflate, *;q=0").finish();
+        assert!(!req.accepts_gzip());
+        assert!(req.accepts_deflate());
+
+        let req =
+            TestRequest::with_header(header::ACCEPT_ENCODING, "identity;q=0, gzip")
+                .finish();
+        assert!(req.accepts_gzip());
+        assert!(!req.accepts_brotli());
+    }
+
+    #[test]
+    fn test_header_count() {
+        let req = TestRequest::with_header(header::CONTENT_LENGTH, "10")
+            .header(header::CONTENT_LENGTH, "20")
+            .header(header::HOST, "example.com")
+            .finish();
+        assert_eq!(req.header_count(header::CONTENT_LENGTH), 2);
+        assert!(req.has_duplicate_header(header::CONTENT_LENGTH));
+        assert_eq!(req.header_count("host"), 1);
+        assert!(!req.has_duplicate_header(header::HOST));
+        assert_eq!(req.header_count(header::COOKIE), 0);
+    }
+
+    #[test]
+    fn test_reject_obs_fold() {
+        let req = TestRequest::with_header("x-test", "value\twith tab").finish();
+        assert!(req.reject_obs_fold().is_ok());
+
+        // `HeaderValue` never contains CR or LF, unfolded continuation
+        // leaves leading whitespace
+        let req = TestRequest::with_header("x-test", "value")
+            .header("x-folded", "  continuation")
+            .finish();
+        match req.reject_obs_fold().err().unwrap() {
+            ParseError::ObsFold(name) => assert_eq!(name, "x-folded"),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_validate_ascii_headers() {
+        let req = TestRequest::with_header("x-test", "value\twith tab").finish();
+        assert!(req.validate_ascii_headers().is_ok());
+
+        let req = TestRequest::with_header("x-test", "value")
+            .header(
+                "x-other",
+                header::HeaderValue::from_bytes(b"caf\xc3\xa9").unwrap(),
+            )
+            .finish();
+        match req.validate_ascii_headers().err().unwrap() {
+            ParseError::NonAsciiHeader(name) => assert_eq!(name, "x-other"),
+            _ => unreachable!("error"),
+        }
+
+        // obs-text
+        let req = TestRequest::with_header(
+            "x-test",
+            header::HeaderValue::from_bytes(b"caf\xe9").unwrap(),
+        )
+        .finish();
+        assert!(req.validate_ascii_headers().is_err());
+    }
+
+    #[test]
+    fn test_require_header() {
+        let req = TestRequest::with_header("x-api-version", "V2").finish();
+        assert!(req.require_header("x-api-version", "V2").is_ok());
+        match req.require_header("x-api-version", "v2") {
+            Err(ParseError::HeaderMismatch) => (),
+            _ => unreachable!("error"),
+        }
+        assert!(req
+            .require_header_ignore_case("x-api-version", "v2")
+            .is_ok());
+        match req.require_header("x-other", "v2") {
+            Err(ParseError::MissingHeader) => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_headers_titlecase() {
+        let req = TestRequest::with_header("content-type", "text/plain")
+            .header("x-request-id", "1")
+            .finish();
+        let mut headers = req.headers_titlecase();
+        headers.sort();
+        assert_eq!(
+            headers,
+            vec![
+                ("Content-Type".to_owned(), "text/plain".to_owned()),
+                ("X-Request-Id".to_owned(), "1".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_referer() {
+        let req = TestRequest::default().finish();
+        assert_eq!(req.referer(), None);
+
+        let req =
+            TestRequest::with_header(header::REFERER, "https://example.com/page?q=1")
+                .finish();
+        assert_eq!(req.referer(), Some("https://example.com/page?q=1"));
+        let url = req.referer_url().unwrap();
+        assert_eq!(url.host_str(), Some("example.com"));
+        assert_eq!(url.path(), "/page");
+
+        let req = TestRequest::with_header(header::REFERER, "not a url").finish();
+        assert_eq!(req.referer(), Some("not a url"));
+        assert!(req.referer_url().is_none());
+    }
+
+    #[test]
+    fn test_websocket_protocols() {
+        let req = TestRequest::default().finish();
+        assert!(req.websocket_protocols().is_empty());
+        assert_eq!(req.select_websocket_protocol(&["chat"]), None);
+
+        let req = TestRequest::with_header(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            "graphql-ws, chat.v2 ,",
+        )
+        .header(header::SEC_WEBSOCKET_PROTOCOL, "chat")
+        .finish();
+        assert_eq!(
+            req.websocket_protocols(),
+            vec!["graphql-ws", "chat.v2", "chat"]
+        );
+        assert_eq!(
+            req.select_websocket_protocol(&["chat", "chat.v2"]),
+            Some("chat.v2".to_owned())
+        );
+        assert_eq!(req.select_websocket_protocol(&["mqtt"]), None);
+    }
+
+    #[test]
+    fn test_content_location() {
+        let req = TestRequest::default().finish();
+        assert_eq!(req.content_location(), None);
+
+        let req = TestRequest::with_header(
+            header::CONTENT_LOCATION,
+            "https://example.com/documents/1",
+        )
+        .finish();
+        assert_eq!(
+            req.content_location(),
+            Some("https://example.com/documents/1")
+        );
+        let url = req.content_location_url().unwrap();
+        assert_eq!(url.path(), "/documents/1");
+
+        let req =
+            TestRequest::with_header(header::CONTENT_LOCATION, "/documents/1").finish();
+        assert_eq!(req.content_location(), Some("/documents/1"));
+        assert!(req.content_location_url().is_none());
+    }
+
+    #[test]
+    fn test_idempotency_key() {
+        let req = TestRequest::default().finish();
+        assert_eq!(req.idempotency_key(), None);
+        assert_eq!(req.idempotency_key_strict().unwrap(), None);
+
+        let req = TestRequest::with_header("idempotency-key", " 8e03978e ").finish();
+        assert_eq!(req.idempotency_key(), Some("8e03978e".to_owned()));
+        assert_eq!(
+            req.idempotency_key_strict().unwrap(),
+            Some("8e03978e".to_owned())
+        );
+
+        let req = TestRequest::with_header("idempotency-key", "8e03 978e").finish();
+        assert_eq!(req.idempotency_key(), Some("8e03 978e".to_owned()));
+        match req.idempotency_key_strict().err().unwrap() {
+            ParseError::Header => (),
+            _ => unreachable!("error"),
+        }
+
+        let long = "a".repeat(256);
+        let req = TestRequest::with_header("idempotency-key", long.as_str()).finish();
+        match req.idempotency_key_strict().err().unwrap() {
+            ParseError::Header => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_cookies_limited() {
+        let req = TestRequest::with_header(header::COOKIE, "a=1; b=2; c=3").finish();
+        match req.cookies_limited(2).err().unwrap() {
+            CookieError::TooMany => (),
+            _ => unreachable!("error"),
+        }
+        let cookies = req.cookies().unwrap();
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(cookies[2].name(), "c");
+        drop(cookies);
+
+        // cached cookies are checked against the limit of every call
+        match req.cookies_limited(2).err().unwrap() {
+            CookieError::TooMany => (),
+            _ => unreachable!("error"),
+        }
+        assert_eq!(req.cookies_limited(3).unwrap().len(), 3);
+
+        let req = TestRequest::with_header(header::COOKIE, "a=1; b=2").finish();
+        assert_eq!(req.cookies_limited(2).unwrap().len(), 2);
+
+        let many = (0..MAX_COOKIES + 1)
+            .map(|i| format!("c{}={}", i, i))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let req = TestRequest::with_header(header::COOKIE, many.as_str()).finish();
+        assert_eq!(req.cookies().unwrap().len(), MAX_COOKIES);
+        match req.cookies_limited(MAX_COOKIES).err().unwrap() {
+            CookieError::TooMany => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_cookies_raw() {
+        let req =
+            TestRequest::with_header(header::COOKIE, "token=a%2Fb%3D; c=1").finish();
+        let cookies = req.cookies_raw().unwrap();
+        assert_eq!(cookies[0].value(), "a%2Fb%3D");
+        assert_eq!(cookies[1].value(), "1");
+        assert_eq!(req.cookie("token").unwrap().value(), "a/b=");
+    }
+
+    #[test]
+    fn test_cookies_with_prefix() {
+        let req = TestRequest::with_header(
+            header::COOKIE,
+            "app.session=1; other=2; app.flash=3",
+        )
+        .finish();
+        let cookies = req.cookies_with_prefix("app.").unwrap();
+        assert_eq!(cookies.len(), 2);
+        assert_eq!(cookies[0].name(), "app.session");
+        assert_eq!(cookies[1].name(), "app.flash");
+        assert!(req.cookies_with_prefix("__Host-").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_raw_cookie_headers() {
+        let req = TestRequest::default().finish();
+        assert!(req.raw_cookie_headers().is_empty());
+
+        let req = TestRequest::with_header(header::COOKIE, "a=1; b=2")
+            .header(
+                header::COOKIE,
+                header::HeaderValue::from_bytes(b"c=\xff").unwrap(),
+            )
+            .header(header::COOKIE, "c=3")
+            .finish();
+        assert_eq!(req.raw_cookie_headers(), vec!["a=1; b=2", "c=3"]);
+    }
+
+    #[test]
+    fn test_cookies_dedup() {
+        let req = TestRequest::with_header(header::COOKIE, "a=1; b=2; a=3")
+            .header(header::COOKIE, "b=4; c=5")
+            .finish();
+
+        let cookies = req.cookies_dedup(DedupPolicy::FirstWins).unwrap();
+        let pairs: Vec<_> = cookies.iter().map(|c| (c.name(), c.value())).collect();
+        assert_eq!(pairs, vec![("a", "1"), ("b", "2"), ("c", "5")]);
+
+        let cookies = req.cookies_dedup(DedupPolicy::LastWins).unwrap();
+        let pairs: Vec<_> = cookies.iter().map(|c| (c.name(), c.value())).collect();
+        assert_eq!(pairs, vec![("a", "3"), ("b", "4"), ("c", "5")]);
+
+        assert_eq!(req.cookies().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_chunked() {
+        let req = TestRequest::default().finish();
+        assert!(!req.chunked().unwrap());
+
+        let req =
+            TestRequest::with_header(header::TRANSFER_ENCODING, "chunked").finish();
+        assert!(req.chunked().unwrap());
+
+        let req = TestRequest::default()
+            .header(
+                header::TRANSFER_ENCODING,
+                Bytes::from_static(b"some va\xadscc\xacas0xsdasdlue"),
+            )
+            .finish();
+        assert!(req.chunked().is_err());
+    }
+
+    #[test]
+    fn test_negotiate_charset() {
+        let available = ["utf-8", "iso-8859-1"];
+
+        let req = TestRequest::default().finish();
+        assert_eq!(req.negotiate_charset(&available), Some("utf-8"));
+
+        let req = TestRequest::with_header("accept-charset", "iso-8859-1, utf-8;q=0.5")
+            .finish();
+        assert_eq!(req.negotiate_charset(&available), Some("iso-8859-1"));
+
+        let req = TestRequest::with_header("accept-charset", "iso-8859-5").finish();
+        assert_eq!(req.negotiate_charset(&available), Some("utf-8"));
+        assert_eq!(req.negotiate_charset(&["iso-8859-1"]), None);
+
+        let req =
+            TestRequest::with_header("accept-charset", "utf-8;q=0, *;q=0.3").finish();
+        assert_eq!(req.negotiate_charset(&available), Some("iso-8859-1"));
+
+        let req = TestRequest::with_header("accept-charset", "*;q=0").finish();
+        assert_eq!(req.negotiate_charset(&available), Some("utf-8"));
+    }
+
+    #[test]
+    fn test_wants_json() {
+        let req = TestRequest::default().finish();
+        assert!(!req.wants_json());
+        let req = TestRequest::with_header("accept", "application/json").finish();
+        assert!(req.wants_json());
+        let req = TestRequest::with_header("accept", "text/html, */*; q=0.1").finish();
+        assert!(req.wants_json());
+        let req = TestRequest::with_header("accept", "text/html").finish();
+        assert!(!req.wants_json());
+        let req =
+            TestRequest::with_header("accept", "*/*, application/json; q=0").finish();
+        assert!(!req.wants_json());
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        let etag = EntityTag::strong("xyzzy".to_owned());
+        let modified =
+            HttpDate::from(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000));
+
+        let req = TestRequest::default().finish();
+        assert_eq!(
+            req.resolve_range(100, Some(&etag), None),
+            RangeDecision::Full
+        );
+
+        let req = TestRequest::with_header(header::RANGE, "bytes=0-9,-10").finish();
+        assert_eq!(
+            req.resolve_range(100, Some(&etag), None),
+            RangeDecision::Partial(vec![(0, 9), (90, 99)])
+        );
+
+        let req =
+            TestRequest::with_header(header::RANGE, "bytes=50-59,0-9,5-19,20-29,0-9")
+                .finish();
+        assert_eq!(
+            req.resolve_range(100, None, None),
+            RangeDecision::Partial(vec![(0, 29), (50, 59)])
+        );
+
+        let many = (0..101)
+            .map(|i| format!("{}-{}", i * 2, i * 2))
+            .collect::<Vec<_>>()
+            .join(",");
+        let req =
+            TestRequest::with_header(header::RANGE, format!("bytes={}", many)).finish();
+        assert_eq!(req.resolve_range(1000, None, None), RangeDecision::Full);
+
+        let req = TestRequest::with_header(header::RANGE, "bytes=200-").finish();
+        assert_eq!(
+            req.resolve_range(100, Some(&etag), None),
+            RangeDecision::NotSatisfiable
+        );
+
+        let req = TestRequest::with_header(header::RANGE, "bytes=0-9")
+            .header(header::IF_RANGE, "\"xyzzy\"")
+            .finish();
+        assert_eq!(
+            req.resolve_range(100, Some(&etag), None),
+            RangeDecision::Partial(vec![(0, 9)])
+        );
+        let other = EntityTag::strong("other".to_owned());
+        assert_eq!(
+            req.resolve_range(100, Some(&other), None),
+            RangeDecision::Full
+        );
+
+        let req = TestRequest::with_header(header::RANGE, "bytes=0-9")
+            .header(header::IF_RANGE, "W/\"xyzzy\"")
+            .finish();
+        assert_eq!(
+            req.resolve_range(100, Some(&etag), None),
+            RangeDecision::Full
+        );
+
+        let req = TestRequest::with_header(header::RANGE, "bytes=0-9")
+            .header(header::IF_RANGE, modified.to_string())
+            .finish();
+        assert_eq!(
+            req.resolve_range(100, None, Some(modified)),
+            RangeDecision::Partial(vec![(0, 9)])
+        );
+        assert_eq!(req.resolve_range(100, None, None), RangeDecision::Full);
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let available = [mime::TEXT_HTML, mime::APPLICATION_JSON];
+
+        let req = TestRequest::default().finish();
+        assert_eq!(req.negotiate(&available), Some(mime::TEXT_HTML));
+
+        let req =
+            TestRequest::with_header("accept", "text/html; q=0.5, application/json")
+                .finish();
+        let n = req.negotiation(&available).unwrap();
+        assert_eq!(n.chosen, mime::APPLICATION_JSON);
+        assert_eq!(n.vary, vec![header::ACCEPT]);
+
+        let req = TestRequest::with_header("accept", "text/*, */*; q=0.1").finish();
+        assert_eq!(req.negotiate(&available), Some(mime::TEXT_HTML));
+
+        let req = TestRequest::with_header("accept", "image/png").finish();
+        assert_eq!(req.negotiate(&available), None);
+
+        let req = TestRequest::with_header("accept", "*/*, text/html; q=0").finish();
+        assert_eq!(req.negotiate(&available), Some(mime::APPLICATION_JSON));
+    }
+
+    impl PartialEq for UrlencodedError {
+        fn eq(&self, other: &UrlencodedError) -> bool {
+            match *self {
+                UrlencodedError::Chunked => match *other {
+                    UrlencodedError::Chunked => true,
+                    _ => false,
+                },
+                UrlencodedError::Overflow => match *other {
+                    UrlencodedError::Overflow => true,
+                    _ => false,
+                },
+                UrlencodedError::OverflowWithData(_) => match *other {
+                    UrlencodedError::OverflowWithData(_) => true,
+                    _ => false,
+                },
+                UrlencodedError::FieldOverflow => match *other {
+                    UrlencodedError::FieldOverflow => true,
+                    _ => false,
+                },
+                UrlencodedError::UnknownLength => match *other {
+                    UrlencodedError::UnknownLength => true,
+                    _ => false,
+                },
+                UrlencodedError::ContentType => match *other {
+                    UrlencodedError::ContentType => true,
+                    _ => false,
+                },
+                _ => false,
+            }
+        }
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Info {
+        hello: String,
+    }
+
+    #[test]
+    fn test_urlencoded_error() {
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(header::CONTENT_LENGTH, "xxxx")
+        .finish();
+        assert_eq!(
+            req.urlencoded::<Info>().poll().err().unwrap(),
+            UrlencodedError::UnknownLength
+        );
+
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(header::CONTENT_LENGTH, "11, 12")
+        .finish();
+        match req.urlencoded::<Info>().poll().err().unwrap() {
+            UrlencodedError::Payload(PayloadError::InvalidContentLength) => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(header::CONTENT_LENGTH, "1000000")
+        .finish();
+        assert_eq!(
+            req.urlencoded::<Info>().poll().err().unwrap(),
+            UrlencodedError::Overflow
+        );
+
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "text/plain")
+            .header(header::CONTENT_LENGTH, "10")
+            .finish();
+        assert_eq!(
+            req.urlencoded::<Info>().poll().err().unwrap(),
+            UrlencodedError::ContentType
+        );
+    }
+
+    #[test]
+    fn test_urlencoded() {
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(header::CONTENT_LENGTH, "11")
+        .set_payload(Bytes::from_static(b"hello=world"))
+        .finish();
+
+        let result = req.urlencoded::<Info>().poll().ok().unwrap();
+        assert_eq!(
+            result,
+            Async::Ready(Info {
+                hello: "world".to_owned()
+            })
+        );
+
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded; charset=utf-8",
+        )
+        .header(header::CONTENT_LENGTH, "11")
+        .set_payload(Bytes::from_static(b"hello=world"))
+        .finish();
+
+        let result = req.urlencoded().poll().ok().unwrap();
+        assert_eq!(
+            result,
+            Async::Ready(Info {
+                hello: "world".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_urlencoded_nested() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct User {
+            name: String,
+            age: u32,
+        }
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Form {
+            user: User,
+        }
+
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .set_payload(Bytes::from_static(b"user[name]=x&user[age]=3"))
+        .finish();
+        let result = req.urlencoded::<Form>().nested(true).poll().ok().unwrap();
+        assert_eq!(
+            result,
+            Async::Ready(Form {
+                user: User {
+                    name: "x".to_owned(),
+                    age: 3,
+                }
+            })
+        );
+
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .set_payload(Bytes::from_static(b"user[name]=x&user[age]=3"))
+        .finish();
+        assert_eq!(
+            req.urlencoded::<Form>().poll().err().unwrap(),
+            UrlencodedError::Parse
+        );
+    }
+
+    #[test]
+    fn test_urlencoded_field_limit() {
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(header::CONTENT_LENGTH, "20")
+        .set_payload(Bytes::from_static(b"hello=world&data=abc"))
+        .finish();
+        assert_eq!(
+            req.urlencoded::<Info>()
+                .field_limit(8)
+                .poll()
+                .err()
+                .unwrap(),
+            UrlencodedError::FieldOverflow
+        );
+
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .header(header::CONTENT_LENGTH, "11")
+        .set_payload(Bytes::from_static(b"hello=world"))
+        .finish();
+        let result = req
+            .urlencoded::<Info>()
+            .field_limit(11)
+            .poll()
+            .ok()
+            .unwrap();
+        assert_eq!(
+            result,
+            Async::Ready(Info {
+                hello: "world".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_urlencoded_into_payload() {
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .set_payload(Bytes::from_static(b"hello=world"))
+        .finish();
+        let mut pl = req.urlencoded::<Info>().into_payload();
+        assert_eq!(
+            pl.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::from_static(b"hello=world")))
+        );
+
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "text/plain")
+            .set_payload(Bytes::from_static(b"hello=world"))
+            .finish();
+        match req.urlencoded::<Info>().into_payload() {
+            Payload::None => (),
+            _ => unreachable!("error"),
+        }
+        let mut body = req.body();
+        match body.poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"hello=world")),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_urlencoded_overflow_data() {
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .set_payload(Bytes::from_static(b"hello=world&other=value"))
+        .finish();
+        match req.urlencoded::<Info>().limit(8).poll().err().unwrap() {
+            UrlencodedError::OverflowWithData(data) => {
+                assert_eq!(data, Bytes::from_static(b"hello=wo"))
+            }
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_urlencoded_lenient_content_type() {
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "text/plain")
+            .set_payload(Bytes::from_static(b"hello=world"))
+            .finish();
+        let result = req.urlencoded::<Info>().poll();
+        assert_eq!(result.err().unwrap(), UrlencodedError::ContentType);
+
+        let result = req
+            .urlencoded::<Info>()
+            .lenient_content_type(&mut req)
+            .poll();
+        assert_eq!(
+            result.ok().unwrap(),
+            Async::Ready(Info {
+                hello: "world".to_owned()
+            })
+        );
+
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded; charset=xxx",
+        )
+        .set_payload(Bytes::from_static(b"hello=world"))
+        .finish();
+        let result = req
+            .urlencoded::<Info>()
+            .lenient_content_type(&mut req)
+            .poll();
+        assert_eq!(result.err().unwrap(), UrlencodedError::ContentType);
+    }
+
+    #[test]
+    fn test_form_field() {
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .set_payload(Bytes::from_static(b"a=1&hello=w%20rld&hello=2"))
+        .finish();
+        assert_eq!(
+            req.form_field("hello").poll().ok().unwrap(),
+            Async::Ready(Some("w rld".to_owned()))
+        );
+
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .set_payload(Bytes::from_static(b"a=1&b=2"))
+        .finish();
+        assert_eq!(req.form_field("c").poll().ok().unwrap(), Async::Ready(None));
+
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "text/plain")
+            .set_payload(Bytes::from_static(b"a=1"))
+            .finish();
+        assert_eq!(
+            req.form_field("a").poll().err().unwrap(),
+            UrlencodedError::ContentType
+        );
+    }
+
+    #[test]
+    fn test_message_body() {
+        let mut req = TestRequest::with_header(header::CONTENT_LENGTH, "xxxx").finish();
+        match req.body().poll().err().unwrap() {
+            PayloadError::UnknownLength => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req =
+            TestRequest::with_header(header::CONTENT_LENGTH, "1000000").finish();
+        match req.body().poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body().poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"11111111111111"))
+            .finish();
+        match req.body().limit(5).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_total_size_guard() {
+        // "x-test" + "value" is 11 bytes
+        let mut req = TestRequest::with_header("x-test", "value")
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.total_size_guard(15).poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header("x-test", "value")
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.total_size_guard(14).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header("x-test", "value").finish();
+        match req.total_size_guard(10).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_body_optional() {
+        let mut req = TestRequest::default().finish();
+        match req.body_optional().poll().ok().unwrap() {
+            Async::Ready(body) => assert!(body.is_none()),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body_optional().poll().ok().unwrap() {
+            Async::Ready(body) => assert_eq!(body, Some(Bytes::from_static(b"test"))),
+            _ => unreachable!("error"),
+        }
+
+        let mut req =
+            TestRequest::with_header(header::CONTENT_LENGTH, "1000000").finish();
+        match req.body_optional().poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_expectations() {
+        let req = TestRequest::default().finish();
+        assert!(req.expectations().is_empty());
+        assert!(!req.expects_continue());
+
+        let req = TestRequest::with_header(header::EXPECT, "100-Continue").finish();
+        assert_eq!(req.expectations(), vec![("100-continue".to_owned(), None)]);
+        assert!(req.expects_continue());
+
+        let req = TestRequest::with_header(header::EXPECT, "foo=\"bar\", baz")
+            .header(header::EXPECT, "qux=1")
+            .finish();
+        assert_eq!(
+            req.expectations(),
+            vec![
+                ("foo".to_owned(), Some("bar".to_owned())),
+                ("baz".to_owned(), None),
+                ("qux".to_owned(), Some("1".to_owned())),
+            ]
+        );
+        assert!(!req.expects_continue());
+    }
+
+    #[test]
+    fn test_is_conditional() {
+        let req = TestRequest::default().finish();
+        assert!(!req.is_conditional());
+
+        for name in &[
+            header::IF_MATCH,
+            header::IF_NONE_MATCH,
+            header::IF_MODIFIED_SINCE,
+            header::IF_UNMODIFIED_SINCE,
+            header::IF_RANGE,
+        ] {
+            let req = TestRequest::with_header(name.clone(), "\"xyzzy\"").finish();
+            assert!(req.is_conditional());
+        }
+    }
+
+    #[test]
+    fn test_keep_alive_params() {
+        let req = TestRequest::default().finish();
+        assert_eq!(req.keep_alive_params(), None);
+
+        let req = TestRequest::with_header("keep-alive", "timeout=5, max=1000").finish();
+        assert_eq!(req.keep_alive_params(), Some((Some(5), Some(1000))));
+
+        let req = TestRequest::with_header("keep-alive", "Timeout=5, max=x").finish();
+        assert_eq!(req.keep_alive_params(), Some((Some(5), None)));
+    }
+
+    #[test]
+    fn test_body_fits() {
+        let req = TestRequest::with_header(header::CONTENT_LENGTH, "100").finish();
+        assert_eq!(req.body_fits(100), Some(true));
+        assert_eq!(req.body_fits(99), Some(false));
+
+        let req =
+            TestRequest::with_header(header::TRANSFER_ENCODING, "chunked").finish();
+        assert_eq!(req.body_fits(100), None);
+
+        let req = TestRequest::with_header(header::CONTENT_LENGTH, "xxx").finish();
+        assert_eq!(req.body_fits(100), None);
+    }
+
+    #[test]
+    fn test_message_body_coalesce() {
+        let mut req = chunked_request(&[&b"abc"[..]; 10]);
+        let body = req.body().coalesce(8).wait().unwrap();
+        assert_eq!(body, Bytes::from("abc".repeat(10)));
+
+        // large chunks are appended after buffered small ones
+        let mut req = chunked_request(&[b"ab", b"cd", b"efghijkl", b"m"]);
+        let body = req.body().coalesce(8).wait().unwrap();
+        assert_eq!(body, Bytes::from_static(b"abcdefghijklm"));
+
+        let mut req = chunked_request(&[&b"abc"[..]; 10]);
+        match req.body().coalesce(64).limit(20).wait().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_message_body_limit_fn() {
+        let quota = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        let q = quota.clone();
+        let mut body = req.body().limit_fn(move || q.get());
+        quota.set(4);
+        match body.poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body().limit_fn(|| 3).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_message_body_min_length() {
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"tes"))
+            .finish();
+        match req.body().min_length(4).poll().err().unwrap() {
+            PayloadError::Incomplete(_) => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body().min_length(4).limit(4).poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test1"))
+            .finish();
+        match req.body().min_length(4).poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test1")),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test1"))
+            .finish();
+        match req.body().min_length(4).limit(4).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_message_body_into_io() {
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        let err = req.body().limit(2).into_io().poll().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_message_body_transform() {
+        let mut req = chunked_request(&[b"ab", b"cd"]);
+        let body = req
+            .body()
+            .transform(|chunk| ok(Bytes::from(chunk.to_ascii_uppercase())))
+            .wait();
+        assert_eq!(body.ok().unwrap(), Bytes::from_static(b"ABCD"));
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        let body = req
+            .body()
+            .limit(6)
+            .transform(|chunk| {
+                let mut doubled = BytesMut::from(chunk.clone());
+                doubled.extend_from_slice(&chunk);
+                ok(doubled.freeze())
+            })
+            .wait();
+        match body.err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        let body = req
+            .body()
+            .transform(|_| err(PayloadError::EncodingCorrupted))
+            .wait();
+        match body.err().unwrap() {
+            PayloadError::EncodingCorrupted => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_message_body_content_length() {
+        let mut req = TestRequest::with_header(header::CONTENT_LENGTH, "  4")
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body().poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header(header::CONTENT_LENGTH, "4, 4")
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body().poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header(header::CONTENT_LENGTH, "  42").finish();
+        match req.body().limit(10).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        for val in &["+42", "-1", "42, 43"] {
+            let mut req =
+                TestRequest::with_header(header::CONTENT_LENGTH, *val).finish();
+            match req.body().poll().err().unwrap() {
+                PayloadError::InvalidContentLength => (),
+                _ => unreachable!("error"),
+            }
+        }
+
+        let mut req = TestRequest::with_header(header::CONTENT_LENGTH, "42")
+            .header(header::CONTENT_LENGTH, "43")
+            .finish();
+        match req.body().poll().err().unwrap() {
+            PayloadError::InvalidContentLength => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_map_err_with() {
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"11111111111111"))
+            .finish();
+        let mut body = req.body().limit(5).map_err_with(|e| format!("body: {}", e));
+        assert_eq!(
+            body.poll().err().unwrap(),
+            "body: A payload reached size limit."
+        );
+
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "text/plain")
+            .set_payload(Bytes::from_static(b"hello=world"))
+            .finish();
+        let mut form = req
+            .urlencoded::<Info>()
+            .map_err_with(|e| format!("form: {}", e));
+        assert_eq!(form.poll().err().unwrap(), "form: Content type error");
+    }
+
+    #[test]
+    fn test_message_body_limit_by_content_type() {
+        let limits = |ct: &str| match ct {
+            "application/json" => 4,
+            _ => 0,
+        };
+
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "application/json")
+            .set_payload(Bytes::from_static(b"{\"a\":1}"))
+            .finish();
+        match req
+            .body()
+            .limit_by_content_type(limits)
+            .poll()
+            .err()
+            .unwrap()
+        {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        // zero falls back to default limit
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "text/plain")
+            .set_payload(Bytes::from_static(b"{\"a\":1}"))
+            .finish();
+        match req
+            .body()
+            .limit(4)
+            .limit_by_content_type(limits)
+            .poll()
+            .ok()
+            .unwrap()
+        {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"{\"a\":1}")),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_message_body_abort() {
+        use std::rc::Rc;
+
+        let rc = Rc::new(());
+        let rc2 = rc.clone();
+        let stream: PayloadStream = Box::new(futures::stream::poll_fn(
+            move || -> Poll<Option<Bytes>, PayloadError> {
+                let _ = &rc2;
+                Ok(Async::NotReady)
+            },
+        ));
+        let mut req = crate::Request::with_payload(Payload::Stream(stream));
+
+        let mut body = req.body();
+        assert!(body.poll().unwrap().is_not_ready());
+        assert_eq!(Rc::strong_count(&rc), 2);
+
+        body.abort();
+        assert_eq!(Rc::strong_count(&rc), 1);
+        match body.poll().err().unwrap() {
+            PayloadError::Incomplete(None) => (),
+            _ => unreachable!("error"),
+        }
+
+        // dropping polled future releases the payload
+        let rc2 = rc.clone();
+        let stream: PayloadStream = Box::new(futures::stream::poll_fn(
+            move || -> Poll<Option<Bytes>, PayloadError> {
+                let _ = &rc2;
+                Ok(Async::NotReady)
+            },
+        ));
+        let mut req = crate::Request::with_payload(Payload::Stream(stream));
+
+        let mut body = req.body();
+        assert!(body.poll().unwrap().is_not_ready());
+        assert_eq!(Rc::strong_count(&rc), 2);
+        drop(body);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    fn test_message_body_on_first_byte() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let calls = Rc::new(Cell::new(0));
+        let calls2 = calls.clone();
+        let mut req = chunked_request(&[b"te", b"st"]);
+        match req
+            .body()
+            .on_first_byte(move |_| calls2.set(calls2.get() + 1))
+            .poll()
+            .ok()
+            .unwrap()
+        {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+        assert_eq!(calls.get(), 1);
+
+        let calls2 = calls.clone();
+        let mut req = TestRequest::default().finish();
+        let _ = req
+            .body()
+            .on_first_byte(move |_| calls2.set(calls2.get() + 1))
+            .poll();
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_message_body_on_percent() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let progress = Rc::new(RefCell::new(Vec::new()));
+        let progress2 = progress.clone();
+        let mut req = chunked_request(&[b"t", b"es", b"t"]);
+        req.headers_mut().insert(
+            header::CONTENT_LENGTH,
+            header::HeaderValue::from_static("4"),
+        );
+        match req
+            .body()
+            .on_percent(move |p| progress2.borrow_mut().push(p))
+            .poll()
+            .ok()
+            .unwrap()
+        {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+        assert_eq!(*progress.borrow(), vec![25, 75, 100]);
+
+        let progress2 = progress.clone();
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        let _ = req
+            .body()
+            .on_percent(move |p| progress2.borrow_mut().push(p))
+            .poll();
+        assert_eq!(progress.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_message_body_decompress() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let data = vec![b'a'; 10_000];
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(&data).unwrap();
+        let enc = e.finish().unwrap();
+
+        let mut req = TestRequest::with_header(header::CONTENT_ENCODING, "gzip")
+            .set_payload(enc.clone())
+            .finish();
+        match req.body().decompress().poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header(header::CONTENT_ENCODING, "gzip")
+            .set_payload(enc)
+            .finish();
+        match req
+            .body()
+            .decompress()
+            .max_decompress_ratio(1000.0)
+            .poll()
+            .ok()
+            .unwrap()
+        {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from(data)),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header(header::CONTENT_ENCODING, "compress")
+            .set_payload(Bytes::from_static(b"data"))
+            .finish();
+        match req.body().decompress().poll().err().unwrap() {
+            PayloadError::EncodingCorrupted => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_verify_content_md5() {
+        let mut req =
+            TestRequest::with_header("content-md5", "CY9rzUYh03PK3k6DJie09g==")
+                .set_payload(Bytes::from_static(b"test"))
+                .finish();
+        match req.verify_content_md5().poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+
+        let mut req =
+            TestRequest::with_header("content-md5", "CY9rzUYh03PK3k6DJie09g==")
+                .set_payload(Bytes::from_static(b"other"))
+                .finish();
+        match req.verify_content_md5().poll().err().unwrap() {
+            PayloadError::ChecksumMismatch => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.verify_content_md5().poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_json_is_valid() {
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "application/json")
+            .set_payload(Bytes::from_static(b"{\"a\": [1, 2, {\"b\": null}]}"))
+            .finish();
+        assert_eq!(req.json_is_valid().poll().ok().unwrap(), Async::Ready(true));
+
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "application/json")
+            .set_payload(Bytes::from_static(b"{\"a\": [1, 2}"))
+            .finish();
+        assert_eq!(
+            req.json_is_valid().poll().ok().unwrap(),
+            Async::Ready(false)
+        );
+
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "text/plain")
+            .set_payload(Bytes::from_static(b"{}"))
+            .finish();
+        assert_eq!(
+            req.json_is_valid().poll().ok().unwrap(),
+            Async::Ready(false)
+        );
+        match req.body().poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"{}")),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_body_utf8() {
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static("тест".as_bytes()))
+            .finish();
+        match req.body_utf8().poll().ok().unwrap() {
+            Async::Ready(s) => assert_eq!(s, "тест"),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"te\xffst"))
+            .finish();
+        match req.body_utf8().poll().err().unwrap() {
+            PayloadError::Utf8(_) => (),
+            _ => unreachable!("error"),
         }
     }
 
-    /// Change max size of payload. By default max size is 256Kb
-    pub fn limit(mut self, limit: usize) -> Self {
-        self.limit = limit;
-        self
+    #[test]
+    fn test_verify_hmac() {
+        let name = HeaderName::from_static("x-hub-signature-256");
+        let signature =
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843";
+
+        let mut req = TestRequest::with_header("x-hub-signature-256", signature)
+            .set_payload(Bytes::from_static(b"what do ya want for nothing?"))
+            .finish();
+        let body = req.verify_hmac(&name, b"Jefe", HmacAlgo::Sha256).wait();
+        assert_eq!(
+            body.ok().unwrap(),
+            Bytes::from_static(b"what do ya want for nothing?")
+        );
+
+        let mut req = TestRequest::with_header("x-hub-signature-256", signature)
+            .set_payload(Bytes::from_static(b"what do ya want for something?"))
+            .finish();
+        match req
+            .verify_hmac(&name, b"Jefe", HmacAlgo::Sha256)
+            .wait()
+            .err()
+            .unwrap()
+        {
+            PayloadError::ChecksumMismatch => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header("x-hub-signature-256", "sha256=zz")
+            .set_payload(Bytes::from_static(b"what do ya want for nothing?"))
+            .finish();
+        match req
+            .verify_hmac(&name, b"Jefe", HmacAlgo::Sha256)
+            .wait()
+            .err()
+            .unwrap()
+        {
+            PayloadError::ChecksumMismatch => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header("x-hub-signature-256", signature)
+            .set_payload(Bytes::from_static(b"what do ya want for nothing?"))
+            .finish();
+        match req
+            .verify_hmac_limited(&name, b"Jefe", HmacAlgo::Sha256, 8)
+            .wait()
+            .err()
+            .unwrap()
+        {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
     }
 
-    fn err(e: PayloadError) -> Self {
-        MessageBody {
-            stream: Payload::None,
-            limit: 262_144,
-            fut: None,
-            err: Some(e),
-            length: None,
+    #[test]
+    fn test_body_with_cookies() {
+        let mut req = TestRequest::with_header(header::COOKIE, "a=1; b=2")
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        let (body, cookies) = req.body_with_cookies().wait().unwrap();
+        assert_eq!(body, Bytes::from_static(b"test"));
+        let pairs: Vec<_> = cookies.iter().map(|c| (c.name(), c.value())).collect();
+        assert_eq!(pairs, vec![("a", "1"), ("b", "2")]);
+
+        let mut req = TestRequest::with_header(header::COOKIE, "invalid")
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        let (body, cookies) = req.body_with_cookies().wait().unwrap();
+        assert_eq!(body, Bytes::from_static(b"test"));
+        assert!(cookies.is_empty());
+    }
+
+    #[test]
+    fn test_body_string_lossy() {
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"te\xffst"))
+            .finish();
+        assert_eq!(req.body_string_lossy().wait().unwrap(), "te\u{FFFD}st");
+
+        let mut req =
+            TestRequest::with_header(header::CONTENT_LENGTH, "1000000").finish();
+        match req.body_string_lossy().wait().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
         }
     }
-}
 
-impl<T> Future for MessageBody<T>
-where
-    T: HttpMessage,
-    T::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
-{
-    type Item = Bytes;
-    type Error = PayloadError;
+    #[test]
+    fn test_body_utf8_transcoded() {
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "text/plain; charset=windows-1252",
+        )
+        .set_payload(Bytes::from_static(b"caf\xe9"))
+        .finish();
+        assert_eq!(req.body_utf8_transcoded().wait().unwrap(), "café");
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static("тест".as_bytes()))
+            .finish();
+        assert_eq!(req.body_utf8_transcoded().wait().unwrap(), "тест");
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"te\xffst"))
+            .finish();
+        match req.body_utf8_transcoded().wait().err().unwrap() {
+            PayloadError::Charset => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req =
+            TestRequest::with_header(header::CONTENT_TYPE, "text/plain; charset=xxx")
+                .finish();
+        match req.body_utf8_transcoded().wait().err().unwrap() {
+            PayloadError::Charset => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_body_segments() {
+        let mut req = chunked_request(&[b"first", b"second"]);
+        match req.body_segments().poll().ok().unwrap() {
+            Async::Ready(segments) => assert_eq!(
+                segments,
+                vec![Bytes::from_static(b"first"), Bytes::from_static(b"second")]
+            ),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body_segments().limit(3).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header(header::CONTENT_LENGTH, "300000")
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body_segments().poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_csrf_token() {
+        let mut req = TestRequest::with_header("x-csrf-token", "token")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .set_payload(Bytes::from_static(b"csrf=other"))
+            .finish();
+        match req
+            .csrf_token("x-csrf-token", Some("csrf"))
+            .poll()
+            .ok()
+            .unwrap()
+        {
+            Async::Ready(token) => assert_eq!(token, Some("token".to_owned())),
+            _ => unreachable!("error"),
+        }
+        if let Payload::None = req.take_payload() {
+            unreachable!("payload is consumed");
+        }
+
+        let mut req = TestRequest::with_header(
+            header::CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        )
+        .set_payload(Bytes::from_static(b"name=test&csrf=other"))
+        .finish();
+        match req
+            .csrf_token("x-csrf-token", Some("csrf"))
+            .poll()
+            .ok()
+            .unwrap()
+        {
+            Async::Ready(token) => assert_eq!(token, Some("other".to_owned())),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default().finish();
+        match req.csrf_token("x-csrf-token", None).poll().ok().unwrap() {
+            Async::Ready(token) => assert_eq!(token, None),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_verify_digest() {
+        let mut req = TestRequest::with_header(
+            "digest",
+            "MD5=CY9rzUYh03PK3k6DJie09g==, \
+             SHA-256=n4bQgYhMfWWaL+qgxVrQFaO/TxsrC4Is0V1sFbDwCgg=",
+        )
+        .set_payload(Bytes::from_static(b"test"))
+        .finish();
+        match req.verify_digest().poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header(
+            "digest",
+            "sha-256=n4bQgYhMfWWaL+qgxVrQFaO/TxsrC4Is0V1sFbDwCgg=",
+        )
+        .set_payload(Bytes::from_static(b"other"))
+        .finish();
+        match req.verify_digest().poll().err().unwrap() {
+            PayloadError::ChecksumMismatch => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header("digest", "MD5=CY9rzUYh03PK3k6DJie09g==")
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.verify_digest().poll().err().unwrap() {
+            PayloadError::ChecksumMismatch => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.verify_digest().poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_body_quoted_printable() {
+        let mut req =
+            chunked_request(&[b"caf=C3=A9 soft=\r\nbreak=3", b"D1 padded=  \n="]);
+        match req.body_quoted_printable().poll().ok().unwrap() {
+            Async::Ready(bytes) => {
+                assert_eq!(bytes, Bytes::from_static(b"caf\xc3\xa9 softbreak=1 padded"))
+            }
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"bad=ZZ"))
+            .finish();
+        match req.body_quoted_printable().poll().err().unwrap() {
+            PayloadError::EncodingCorrupted => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"truncated=A"))
+            .finish();
+        match req.body_quoted_printable().poll().err().unwrap() {
+            PayloadError::EncodingCorrupted => (),
+            _ => unreachable!("error"),
+        }
+
+        // endless transport padding is not buffered
+        let mut chunks: Vec<&'static [u8]> = vec![b"a="];
+        chunks.extend((0..100).map(|_| &b" "[..]));
+        let mut req = chunked_request(&chunks);
+        match req.body_quoted_printable().poll().err().unwrap() {
+            PayloadError::EncodingCorrupted => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_body_no_nul() {
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body_no_nul().poll().ok().unwrap() {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"te\x00st"))
+            .finish();
+        match req.body_no_nul().poll().err().unwrap() {
+            PayloadError::InvalidByte => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body_no_nul_limited(3).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        // content length is checked before payload is taken
+        let mut req = TestRequest::with_header(header::CONTENT_LENGTH, "100")
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req.body_no_nul_limited(10).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+        match req.take_payload() {
+            Payload::None => unreachable!("error"),
+            _ => (),
+        }
+    }
+
+    #[test]
+    fn test_body_timed() {
+        let start = Instant::now();
+        let mut req = chunked_request(&[b"line1", b"line2"]);
+        let chunks = req.body_timed().wait().unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].0, Bytes::from_static(b"line1"));
+        assert_eq!(chunks[1].0, Bytes::from_static(b"line2"));
+        assert!(start <= chunks[0].1 && chunks[0].1 <= chunks[1].1);
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from(vec![0; 262_145]))
+            .finish();
+        match req.body_timed().wait().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = chunked_request(&[b"line1", b"line2"]);
+        match req.body_timed_limited(8).wait().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_body_with_stats() {
+        let mut req = chunked_request(&[b"line1\nli", b"ne2\n"]);
+        match req.body_with_stats().poll().ok().unwrap() {
+            Async::Ready((body, stats)) => {
+                assert_eq!(body, Bytes::from_static(b"line1\nline2\n"));
+                assert_eq!(
+                    stats,
+                    BodyStats {
+                        bytes: 12,
+                        lines: 2,
+                        chunks: 2,
+                    }
+                );
+            }
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default().finish();
+        match req.body_with_stats().poll().ok().unwrap() {
+            Async::Ready((body, stats)) => {
+                assert!(body.is_empty());
+                assert_eq!(stats, BodyStats::default());
+            }
+            _ => unreachable!("error"),
+        }
+
+        let mut req = chunked_request(&[
+            b"line1
+li",
+            b"ne2
+",
+        ]);
+        match req.body_with_stats_limited(10).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_sniff_and_parse() {
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "text/plain")
+            .set_payload(Bytes::from_static(b" [1, 2]"))
+            .finish();
+        match req.sniff_and_parse::<Vec<u8>>().poll().ok().unwrap() {
+            Async::Ready(v) => assert_eq!(v, vec![1, 2]),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"\x92\x01\x02"))
+            .finish();
+        match req.sniff_and_parse::<Vec<u8>>().poll().ok().unwrap() {
+            Async::Ready(v) => assert_eq!(v, vec![1, 2]),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "application/json")
+            .set_payload(Bytes::from_static(b"\"str\""))
+            .finish();
+        match req.sniff_and_parse::<String>().poll().ok().unwrap() {
+            Async::Ready(v) => assert_eq!(v, "str"),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"plain"))
+            .finish();
+        match req.sniff_and_parse::<String>().poll().err().unwrap() {
+            SniffError::UnknownFormat => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_body_expect_hash() {
+        let expected = Sha256::digest(b"test");
+        let mut req = chunked_request(&[b"te", b"st"]);
+        match req
+            .body_expect_hash(HashAlgo::Sha256, &expected)
+            .poll()
+            .ok()
+            .unwrap()
+        {
+            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
+            _ => unreachable!("error"),
+        }
+
+        let expected = Sha512::digest(b"test");
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"other"))
+            .finish();
+        match req
+            .body_expect_hash(HashAlgo::Sha512, &expected)
+            .poll()
+            .err()
+            .unwrap()
+        {
+            PayloadError::ChecksumMismatch => (),
+            _ => unreachable!("error"),
+        }
+
+        let expected = Sha256::digest(b"test");
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test"))
+            .finish();
+        match req
+            .body_expect_hash_limited(HashAlgo::Sha256, &expected, 3)
+            .poll()
+            .err()
+            .unwrap()
+        {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        if let Some(ref mut fut) = self.fut {
-            return fut.poll();
+    #[test]
+    fn test_body_with_entropy() {
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"aaaa"))
+            .finish();
+        match req.body_with_entropy().poll().ok().unwrap() {
+            Async::Ready((body, entropy)) => {
+                assert_eq!(body, Bytes::from_static(b"aaaa"));
+                assert_eq!(entropy, 0.0);
+            }
+            _ => unreachable!("error"),
         }
 
-        if let Some(err) = self.err.take() {
-            return Err(err);
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"abcd"))
+            .finish();
+        match req.body_with_entropy().poll().ok().unwrap() {
+            Async::Ready((_, entropy)) => assert!((entropy - 2.0).abs() < 1e-9),
+            _ => unreachable!("error"),
         }
 
-        if let Some(len) = self.length.take() {
-            if len > self.limit {
-                return Err(PayloadError::Overflow);
+        let mut req = TestRequest::default().finish();
+        match req.body_with_entropy().poll().ok().unwrap() {
+            Async::Ready((body, entropy)) => {
+                assert!(body.is_empty());
+                assert_eq!(entropy, 0.0);
             }
+            _ => unreachable!("error"),
         }
 
-        // future
-        let limit = self.limit;
-        self.fut = Some(Box::new(
-            std::mem::replace(&mut self.stream, Payload::None)
-                .from_err()
-                .fold(BytesMut::with_capacity(8192), move |mut body, chunk| {
-                    if (body.len() + chunk.len()) > limit {
-                        Err(PayloadError::Overflow)
-                    } else {
-                        body.extend_from_slice(&chunk);
-                        Ok(body)
-                    }
-                })
-                .map(|body| body.freeze()),
-        ));
-        self.poll()
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"abcd"))
+            .finish();
+        match req.body_with_entropy_limited(3).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
     }
-}
 
-/// Future that resolves to a parsed urlencoded values.
-pub struct UrlEncoded<T: HttpMessage, U> {
-    stream: Payload<T::Stream>,
-    limit: usize,
-    length: Option<usize>,
-    encoding: EncodingRef,
-    err: Option<UrlencodedError>,
-    fut: Option<Box<Future<Item = U, Error = UrlencodedError>>>,
-}
+    #[test]
+    fn test_forward_payload() {
+        let mut req = chunked_request(&[b"first", b"second"]);
+        let (tx, rx) = futures::sync::mpsc::unbounded();
+        assert_eq!(req.forward_payload(tx).wait().ok().unwrap(), 11);
+        assert_eq!(
+            rx.collect().wait().unwrap(),
+            vec![Bytes::from_static(b"first"), Bytes::from_static(b"second")]
+        );
 
-impl<T, U> UrlEncoded<T, U>
-where
-    T: HttpMessage,
-    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
-{
-    /// Create a new future to URL encode a request
-    pub fn new(req: &mut T) -> UrlEncoded<T, U> {
-        // check content type
-        if req.content_type().to_lowercase() != "application/x-www-form-urlencoded" {
-            return Self::err(UrlencodedError::ContentType);
+        let stream: PayloadStream = Box::new(futures::stream::iter_result(vec![
+            Ok(Bytes::from_static(b"first")),
+            Err(PayloadError::Incomplete(None)),
+        ]));
+        let mut req = crate::Request::with_payload(Payload::Stream(stream));
+        let (tx, _rx) = futures::sync::mpsc::unbounded();
+        match req.forward_payload(tx).wait().err().unwrap() {
+            ForwardError::Payload(PayloadError::Incomplete(None)) => (),
+            _ => unreachable!("error"),
         }
-        let encoding = match req.encoding() {
-            Ok(enc) => enc,
-            Err(_) => return Self::err(UrlencodedError::ContentType),
-        };
+    }
 
-        let mut len = None;
-        if let Some(l) = req.headers().get(header::CONTENT_LENGTH) {
-            if let Ok(s) = l.to_str() {
-                if let Ok(l) = s.parse::<usize>() {
-                    len = Some(l)
-                } else {
-                    return Self::err(UrlencodedError::UnknownLength);
-                }
-            } else {
-                return Self::err(UrlencodedError::UnknownLength);
-            }
-        };
+    #[test]
+    fn test_count_lines() {
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"line1\nline2\nline3"))
+            .finish();
+        assert_eq!(req.count_lines(1024).poll().ok().unwrap(), Async::Ready(3));
 
-        UrlEncoded {
-            encoding,
-            stream: req.take_payload(),
-            limit: 262_144,
-            length: len,
-            fut: None,
-            err: None,
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"line1\nline2\n"))
+            .finish();
+        assert_eq!(req.count_lines(1024).poll().ok().unwrap(), Async::Ready(2));
+
+        let mut req = TestRequest::default().finish();
+        assert_eq!(req.count_lines(1024).poll().ok().unwrap(), Async::Ready(0));
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"line1\nline2\n"))
+            .finish();
+        match req.count_lines(5).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
         }
     }
 
-    fn err(e: UrlencodedError) -> Self {
-        UrlEncoded {
-            stream: Payload::None,
-            limit: 262_144,
-            fut: None,
-            err: Some(e),
-            length: None,
-            encoding: UTF_8,
+    #[test]
+    fn test_readlines() {
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(
+                b"Lorem Ipsum is simply dummy text of the printing and typesetting\n\
+                  industry. Lorem Ipsum has been the industry's standard dummy\n\
+                  Contrary to popular belief, Lorem Ipsum is not simply random text.",
+            ))
+            .finish();
+        let mut r = Readlines::new(&mut req);
+        match r.poll().ok().unwrap() {
+            Async::Ready(Some(s)) => assert_eq!(
+                s,
+                "Lorem Ipsum is simply dummy text of the printing and typesetting\n"
+            ),
+            _ => unreachable!("error"),
+        }
+        match r.poll().ok().unwrap() {
+            Async::Ready(Some(s)) => assert_eq!(
+                s,
+                "industry. Lorem Ipsum has been the industry's standard dummy\n"
+            ),
+            _ => unreachable!("error"),
+        }
+        match r.poll().ok().unwrap() {
+            Async::Ready(Some(s)) => assert_eq!(
+                s,
+                "Contrary to popular belief, Lorem Ipsum is not simply random text."
+            ),
+            _ => unreachable!("error"),
         }
     }
 
-    /// Change max size of payload. By default max size is 256Kb
-    pub fn limit(mut self, limit: usize) -> Self {
-        self.limit = limit;
-        self
+    #[test]
+    fn test_body_reader() {
+        use std::io::Read;
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test body"))
+            .finish();
+        let mut reader = req.body_reader();
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"test");
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, " body");
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"test body"))
+            .finish();
+        let mut reader = req.body_reader().limit(4);
+        let err = reader.read(&mut buf).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
-}
 
-impl<T, U> Future for UrlEncoded<T, U>
-where
-    T: HttpMessage,
-    T::Stream: Stream<Item = Bytes, Error = PayloadError> + 'static,
-    U: DeserializeOwned + 'static,
-{
-    type Item = U;
-    type Error = UrlencodedError;
+    #[test]
+    fn test_body_str_chunks() {
+        let mut req = chunked_request(&[b"caf\xc3", b"\xa9 \xe2\x82", b"\xac"]);
+        let mut chunks = req.body_str_chunks();
+        assert_eq!(
+            chunks.poll().ok().unwrap(),
+            Async::Ready(Some("caf".to_owned()))
+        );
+        assert_eq!(
+            chunks.poll().ok().unwrap(),
+            Async::Ready(Some("é ".to_owned()))
+        );
+        assert_eq!(
+            chunks.poll().ok().unwrap(),
+            Async::Ready(Some("€".to_owned()))
+        );
+        assert_eq!(chunks.poll().ok().unwrap(), Async::Ready(None));
 
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        if let Some(ref mut fut) = self.fut {
-            return fut.poll();
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"te\xffst"))
+            .finish();
+        match req.body_str_chunks().poll().err().unwrap() {
+            PayloadError::Utf8(_) => (),
+            _ => unreachable!("error"),
         }
 
-        if let Some(err) = self.err.take() {
-            return Err(err);
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"caf\xc3"))
+            .finish();
+        let mut chunks = req.body_str_chunks();
+        assert_eq!(
+            chunks.poll().ok().unwrap(),
+            Async::Ready(Some("caf".to_owned()))
+        );
+        match chunks.poll().err().unwrap() {
+            PayloadError::Utf8(_) => (),
+            _ => unreachable!("error"),
         }
+    }
 
-        // payload size
-        let limit = self.limit;
-        if let Some(len) = self.length.take() {
-            if len > limit {
-                return Err(UrlencodedError::Overflow);
-            }
+    #[test]
+    fn test_split_body() {
+        let mut req = chunked_request(&[b"one\r\n", b"\r\ntwo\r\n\r", b"\nthree"]);
+        let mut records = req.split_body(b"\r\n\r\n", 1024);
+        assert_eq!(
+            records.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::from_static(b"one")))
+        );
+        assert_eq!(
+            records.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::from_static(b"two")))
+        );
+        assert_eq!(
+            records.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::from_static(b"three")))
+        );
+        assert_eq!(records.poll().ok().unwrap(), Async::Ready(None));
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"datadata||x"))
+            .finish();
+        let mut records = req.split_body(b"||", 1024).record_limit(4);
+        match records.poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
         }
 
-        // future
-        let encoding = self.encoding;
-        let fut = std::mem::replace(&mut self.stream, Payload::None)
-            .from_err()
-            .fold(BytesMut::with_capacity(8192), move |mut body, chunk| {
-                if (body.len() + chunk.len()) > limit {
-                    Err(UrlencodedError::Overflow)
-                } else {
-                    body.extend_from_slice(&chunk);
-                    Ok(body)
-                }
-            })
-            .and_then(move |body| {
-                if (encoding as *const Encoding) == UTF_8 {
-                    serde_urlencoded::from_bytes::<U>(&body)
-                        .map_err(|_| UrlencodedError::Parse)
-                } else {
-                    let body = encoding
-                        .decode(&body, DecoderTrap::Strict)
-                        .map_err(|_| UrlencodedError::Parse)?;
-                    serde_urlencoded::from_str::<U>(&body)
-                        .map_err(|_| UrlencodedError::Parse)
-                }
-            });
-        self.fut = Some(Box::new(fut));
-        self.poll()
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"data||data"))
+            .finish();
+        let mut records = req.split_body(b"||", 8);
+        match records.poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"data"))
+            .finish();
+        let mut records = req.split_body(b"||", usize::max_value());
+        assert_eq!(
+            records.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::from_static(b"data")))
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use encoding::all::ISO_8859_2;
-    use encoding::Encoding;
-    use futures::Async;
-    use mime;
-    use serde_derive::Deserialize;
+    #[test]
+    fn test_body_until() {
+        let mut req = chunked_request(&[b"head\r", b"\n\r\nbo", b"dy"]);
+        let (before, after) = req.body_until(b"\r\n\r\n", 1024).wait().unwrap();
+        assert_eq!(before, Bytes::from_static(b"head"));
+        assert_eq!(after.concat2().wait().unwrap(), Bytes::from_static(b"body"));
 
-    use super::*;
-    use crate::test::TestRequest;
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"head"))
+            .finish();
+        match req.body_until(b"||", 1024).poll().err().unwrap() {
+            PayloadError::Incomplete(None) => (),
+            _ => unreachable!("error"),
+        }
 
-    #[test]
-    fn test_content_type() {
-        let req = TestRequest::with_header("content-type", "text/plain").finish();
-        assert_eq!(req.content_type(), "text/plain");
-        let req =
-            TestRequest::with_header("content-type", "application/json; charset=utf=8")
-                .finish();
-        assert_eq!(req.content_type(), "application/json");
-        let req = TestRequest::default().finish();
-        assert_eq!(req.content_type(), "");
-    }
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"header||body"))
+            .finish();
+        match req.body_until(b"||", 4).poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
 
-    #[test]
-    fn test_mime_type() {
-        let req = TestRequest::with_header("content-type", "application/json").finish();
-        assert_eq!(req.mime_type().unwrap(), Some(mime::APPLICATION_JSON));
-        let req = TestRequest::default().finish();
-        assert_eq!(req.mime_type().unwrap(), None);
-        let req =
-            TestRequest::with_header("content-type", "application/json; charset=utf-8")
-                .finish();
-        let mt = req.mime_type().unwrap().unwrap();
-        assert_eq!(mt.get_param(mime::CHARSET), Some(mime::UTF_8));
-        assert_eq!(mt.type_(), mime::APPLICATION);
-        assert_eq!(mt.subtype(), mime::JSON);
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"head"))
+            .finish();
+        match req
+            .body_until(b"||", usize::max_value())
+            .poll()
+            .err()
+            .unwrap()
+        {
+            PayloadError::Incomplete(None) => (),
+            _ => unreachable!("error"),
+        }
     }
 
     #[test]
-    fn test_mime_type_error() {
-        let req = TestRequest::with_header(
-            "content-type",
-            "applicationadfadsfasdflknadsfklnadsfjson",
-        )
-        .finish();
-        assert_eq!(Err(ContentTypeError::ParseError), req.mime_type());
+    fn test_split_headers_body() {
+        let mut req = chunked_request(&[
+            b"Content-Type: text/plain\r\nX-Id: 1\r",
+            b"\n\r\nhello ",
+            b"world",
+        ]);
+        let (headers, body) = req.split_headers_body().wait().unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[header::CONTENT_TYPE], "text/plain");
+        assert_eq!(headers["x-id"], "1");
+        assert_eq!(body, Bytes::from_static(b"hello world"));
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"X-Id: 1\r\n"))
+            .finish();
+        match req.split_headers_body().wait().err().unwrap() {
+            ParseError::Incomplete => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"Bad Header\r\n\r\n"))
+            .finish();
+        match req.split_headers_body().wait().err().unwrap() {
+            ParseError::Header => (),
+            _ => unreachable!("error"),
+        }
     }
 
     #[test]
-    fn test_encoding() {
-        let req = TestRequest::default().finish();
-        assert_eq!(UTF_8.name(), req.encoding().unwrap().name());
+    fn test_csp_report() {
+        let mut req =
+            TestRequest::with_header(header::CONTENT_TYPE, "application/csp-report")
+                .set_payload(Bytes::from_static(
+                    b"{\"csp-report\": {\"document-uri\": \"https://example.com/\", \
+                      \"violated-directive\": \"script-src 'self'\", \
+                      \"blocked-uri\": \"https://evil.com/x.js\"}}",
+                ))
+                .finish();
+        let report = req.csp_report().wait().unwrap();
+        assert_eq!(report.document_uri, "https://example.com/");
+        assert_eq!(report.violated_directive.unwrap(), "script-src 'self'");
+        assert_eq!(report.blocked_uri.unwrap(), "https://evil.com/x.js");
 
-        let req = TestRequest::with_header("content-type", "application/json").finish();
-        assert_eq!(UTF_8.name(), req.encoding().unwrap().name());
+        let mut req =
+            TestRequest::with_header(header::CONTENT_TYPE, "application/reports+json")
+                .set_payload(Bytes::from_static(
+                    b"[{\"type\": \"deprecation\", \"body\": {}}, \
+                      {\"type\": \"csp-violation\", \"body\": \
+                      {\"documentURL\": \"https://example.com/\", \
+                      \"effectiveDirective\": \"img-src\", \"lineNumber\": 7}}]",
+                ))
+                .finish();
+        let report = req.csp_report().wait().unwrap();
+        assert_eq!(report.document_uri, "https://example.com/");
+        assert_eq!(report.effective_directive.unwrap(), "img-src");
+        assert_eq!(report.line_number, Some(7));
 
-        let req = TestRequest::with_header(
-            "content-type",
-            "application/json; charset=ISO-8859-2",
-        )
-        .finish();
-        assert_eq!(ISO_8859_2.name(), req.encoding().unwrap().name());
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "application/json")
+            .set_payload(Bytes::from_static(b"{}"))
+            .finish();
+        match req.csp_report().wait().err().unwrap() {
+            JsonPayloadError::ContentType => (),
+            _ => unreachable!("error"),
+        }
     }
 
     #[test]
-    fn test_encoding_error() {
-        let req = TestRequest::with_header("content-type", "applicatjson").finish();
-        assert_eq!(Some(ContentTypeError::ParseError), req.encoding().err());
+    fn test_csv_stream() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Row {
+            name: String,
+            note: String,
+        }
 
-        let req = TestRequest::with_header(
-            "content-type",
-            "application/json; charset=kkkttktk",
-        )
-        .finish();
+        let mut req = chunked_request(&[
+            b"name,note\nfirst,\"multi",
+            b"\nline\"\nsec",
+            b"ond,plain",
+        ]);
+        let mut rows = req.csv_stream::<Row>();
         assert_eq!(
-            Some(ContentTypeError::UnknownEncoding),
-            req.encoding().err()
+            rows.poll().ok().unwrap(),
+            Async::Ready(Some(Row {
+                name: "first".to_owned(),
+                note: "multi\nline".to_owned(),
+            }))
         );
-    }
+        assert_eq!(
+            rows.poll().ok().unwrap(),
+            Async::Ready(Some(Row {
+                name: "second".to_owned(),
+                note: "plain".to_owned(),
+            }))
+        );
+        assert_eq!(rows.poll().ok().unwrap(), Async::Ready(None));
 
-    #[test]
-    fn test_chunked() {
-        let req = TestRequest::default().finish();
-        assert!(!req.chunked().unwrap());
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"1,2\n3,4\n"))
+            .finish();
+        let mut rows = req.csv_stream::<(u32, u32)>().has_headers(false);
+        assert_eq!(rows.poll().ok().unwrap(), Async::Ready(Some((1, 2))));
+        assert_eq!(rows.poll().ok().unwrap(), Async::Ready(Some((3, 4))));
+        assert_eq!(rows.poll().ok().unwrap(), Async::Ready(None));
 
-        let req =
-            TestRequest::with_header(header::TRANSFER_ENCODING, "chunked").finish();
-        assert!(req.chunked().unwrap());
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"name,note\nfirst,too long\n"))
+            .finish();
+        let mut rows = req.csv_stream::<Row>().record_limit(10);
+        match rows.poll().err().unwrap() {
+            CsvError::Overflow => (),
+            _ => unreachable!("error"),
+        }
 
-        let req = TestRequest::default()
-            .header(
-                header::TRANSFER_ENCODING,
-                Bytes::from_static(b"some va\xadscc\xacas0xsdasdlue"),
-            )
+        // empty fields are counted against the record limit
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from(",".repeat(1000)))
             .finish();
-        assert!(req.chunked().is_err());
-    }
+        let mut rows = req
+            .csv_stream::<Vec<String>>()
+            .has_headers(false)
+            .record_limit(100);
+        match rows.poll().err().unwrap() {
+            CsvError::Overflow => (),
+            _ => unreachable!("error"),
+        }
 
-    impl PartialEq for UrlencodedError {
-        fn eq(&self, other: &UrlencodedError) -> bool {
-            match *self {
-                UrlencodedError::Chunked => match *other {
-                    UrlencodedError::Chunked => true,
-                    _ => false,
-                },
-                UrlencodedError::Overflow => match *other {
-                    UrlencodedError::Overflow => true,
-                    _ => false,
-                },
-                UrlencodedError::UnknownLength => match *other {
-                    UrlencodedError::UnknownLength => true,
-                    _ => false,
-                },
-                UrlencodedError::ContentType => match *other {
-                    UrlencodedError::ContentType => true,
-                    _ => false,
-                },
-                _ => false,
-            }
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from("1,2\n".repeat(70_000)))
+            .finish();
+        let mut rows = req.csv_stream::<(u32, u32)>().has_headers(false);
+        match rows.poll().err().unwrap() {
+            CsvError::Overflow => (),
+            _ => unreachable!("error"),
         }
-    }
 
-    #[derive(Deserialize, Debug, PartialEq)]
-    struct Info {
-        hello: String,
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"a\nx\n"))
+            .finish();
+        let mut rows = req.csv_stream::<(u32,)>().has_headers(false);
+        match rows.poll().err().unwrap() {
+            CsvError::Deserialize(_) => (),
+            _ => unreachable!("error"),
+        }
     }
 
     #[test]
-    fn test_urlencoded_error() {
-        let mut req = TestRequest::with_header(
-            header::CONTENT_TYPE,
-            "application/x-www-form-urlencoded",
-        )
-        .header(header::CONTENT_LENGTH, "xxxx")
-        .finish();
+    fn test_length_prefixed_frames() {
+        let mut req = chunked_request(&[b"\x00\x04da", b"ta\x00\x00\x00", b"\x01x"]);
+        let mut frames = req.length_prefixed_frames(LengthPrefix::U16Be);
         assert_eq!(
-            req.urlencoded::<Info>().poll().err().unwrap(),
-            UrlencodedError::UnknownLength
+            frames.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::from_static(b"data")))
         );
-
-        let mut req = TestRequest::with_header(
-            header::CONTENT_TYPE,
-            "application/x-www-form-urlencoded",
-        )
-        .header(header::CONTENT_LENGTH, "1000000")
-        .finish();
         assert_eq!(
-            req.urlencoded::<Info>().poll().err().unwrap(),
-            UrlencodedError::Overflow
+            frames.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::new()))
+        );
+        assert_eq!(
+            frames.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::from_static(b"x")))
         );
+        assert_eq!(frames.poll().ok().unwrap(), Async::Ready(None));
 
-        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "text/plain")
-            .header(header::CONTENT_LENGTH, "10")
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"\x08\x00\x00\x00data"))
+            .finish();
+        let mut frames = req.length_prefixed_frames(LengthPrefix::U32Le);
+        match frames.poll().err().unwrap() {
+            PayloadError::Incomplete(None) => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"\x00\x08datadata"))
             .finish();
+        let mut frames = req
+            .length_prefixed_frames(LengthPrefix::U16Be)
+            .frame_limit(4);
+        match frames.poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_sse_events() {
+        let mut req = chunked_request(&[
+            b": comment\nevent: update\nid: 1\nda",
+            b"ta: first\r\ndata:second\nretry: 300\n\n",
+            b"id: 2\n\ndata\n\ndata: partial",
+        ]);
+        let mut events = req.sse_events();
         assert_eq!(
-            req.urlencoded::<Info>().poll().err().unwrap(),
-            UrlencodedError::ContentType
+            events.poll().ok().unwrap(),
+            Async::Ready(Some(SseEvent {
+                event: Some("update".to_owned()),
+                data: "first\nsecond".to_owned(),
+                id: Some("1".to_owned()),
+                retry: Some(300),
+            }))
         );
+        // last event id and retry persist across events
+        assert_eq!(
+            events.poll().ok().unwrap(),
+            Async::Ready(Some(SseEvent {
+                event: None,
+                data: String::new(),
+                id: Some("2".to_owned()),
+                retry: Some(300),
+            }))
+        );
+        assert_eq!(events.poll().ok().unwrap(), Async::Ready(None));
     }
 
     #[test]
-    fn test_urlencoded() {
-        let mut req = TestRequest::with_header(
-            header::CONTENT_TYPE,
-            "application/x-www-form-urlencoded",
-        )
-        .header(header::CONTENT_LENGTH, "11")
-        .set_payload(Bytes::from_static(b"hello=world"))
-        .finish();
+    fn test_sse_events_limit() {
+        let mut req = chunked_request(&[b"data:123\ndata:456\n", b"data:789\n\n"]);
+        let mut events = req.sse_events().limit(9);
+        match events.poll().err().unwrap() {
+            ReadlinesError::LimitOverflow => (),
+            _ => unreachable!("error"),
+        }
 
-        let result = req.urlencoded::<Info>().poll().ok().unwrap();
+        let mut req = chunked_request(&[b"data:123\ndata:456\n\n"]);
+        let mut events = req.sse_events().limit(9);
         assert_eq!(
-            result,
-            Async::Ready(Info {
-                hello: "world".to_owned()
-            })
+            events.poll().ok().unwrap(),
+            Async::Ready(Some(SseEvent {
+                data: "123\n456".to_owned(),
+                ..SseEvent::default()
+            }))
         );
+    }
 
-        let mut req = TestRequest::with_header(
-            header::CONTENT_TYPE,
-            "application/x-www-form-urlencoded; charset=utf-8",
-        )
-        .header(header::CONTENT_LENGTH, "11")
-        .set_payload(Bytes::from_static(b"hello=world"))
-        .finish();
-
-        let result = req.urlencoded().poll().ok().unwrap();
+    #[test]
+    fn test_take_payload_owned() {
+        let stream = {
+            let mut req = TestRequest::default()
+                .set_payload(Bytes::from_static(b"test"))
+                .finish();
+            req.take_payload_owned()
+        };
         assert_eq!(
-            result,
-            Async::Ready(Info {
-                hello: "world".to_owned()
-            })
+            stream.concat2().wait().ok().unwrap(),
+            Bytes::from_static(b"test")
         );
     }
 
     #[test]
-    fn test_message_body() {
-        let mut req = TestRequest::with_header(header::CONTENT_LENGTH, "xxxx").finish();
-        match req.body().poll().err().unwrap() {
-            PayloadError::UnknownLength => (),
-            _ => unreachable!("error"),
-        }
+    fn test_readlines_partial_line() {
+        // partial line buffered from previous chunk is part of the line
+        let mut req = chunked_request(&[b"hel", b"lo\n"]);
+        let mut r = req.readlines();
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("hello\n".to_owned()))
+        );
+        assert_eq!(r.poll().ok().unwrap(), Async::Ready(None));
 
-        let mut req =
-            TestRequest::with_header(header::CONTENT_LENGTH, "1000000").finish();
-        match req.body().poll().err().unwrap() {
-            PayloadError::Overflow => (),
+        // limit applies to the whole line
+        let mut req = chunked_request(&[b"hel", b"lo\n"]);
+        let mut r = req.readlines().limit(4);
+        match r.poll().err().unwrap() {
+            ReadlinesError::LimitOverflow => (),
             _ => unreachable!("error"),
         }
+    }
 
+    #[test]
+    fn test_readlines_with_prefix() {
         let mut req = TestRequest::default()
-            .set_payload(Bytes::from_static(b"test"))
+            .set_payload(Bytes::from_static(b"lo\nworld"))
             .finish();
-        match req.body().poll().ok().unwrap() {
-            Async::Ready(bytes) => assert_eq!(bytes, Bytes::from_static(b"test")),
-            _ => unreachable!("error"),
-        }
+        let mut r = req
+            .readlines()
+            .with_prefix(BytesMut::from(&b"first\nhel"[..]));
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("first\n".to_owned()))
+        );
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("hello\n".to_owned()))
+        );
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("world".to_owned()))
+        );
+        assert_eq!(r.poll().ok().unwrap(), Async::Ready(None));
+    }
 
+    #[test]
+    fn test_readlines_trim() {
         let mut req = TestRequest::default()
-            .set_payload(Bytes::from_static(b"11111111111111"))
+            .set_payload(Bytes::from_static(b"  first line \r\nsecond\t\n last "))
             .finish();
-        match req.body().limit(5).poll().err().unwrap() {
-            PayloadError::Overflow => (),
-            _ => unreachable!("error"),
-        }
+        let mut r = Readlines::new(&mut req).trim(true);
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("first line".to_owned()))
+        );
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("second".to_owned()))
+        );
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("last".to_owned()))
+        );
+        assert_eq!(r.poll().ok().unwrap(), Async::Ready(None));
     }
 
     #[test]
-    fn test_readlines() {
+    fn test_readlines_require_trailing_newline() {
         let mut req = TestRequest::default()
-            .set_payload(Bytes::from_static(
-                b"Lorem Ipsum is simply dummy text of the printing and typesetting\n\
-                  industry. Lorem Ipsum has been the industry's standard dummy\n\
-                  Contrary to popular belief, Lorem Ipsum is not simply random text.",
-            ))
+            .set_payload(Bytes::from_static(b"first\nlast"))
             .finish();
-        let mut r = Readlines::new(&mut req);
-        match r.poll().ok().unwrap() {
-            Async::Ready(Some(s)) => assert_eq!(
-                s,
-                "Lorem Ipsum is simply dummy text of the printing and typesetting\n"
-            ),
-            _ => unreachable!("error"),
-        }
-        match r.poll().ok().unwrap() {
-            Async::Ready(Some(s)) => assert_eq!(
-                s,
-                "industry. Lorem Ipsum has been the industry's standard dummy\n"
-            ),
-            _ => unreachable!("error"),
-        }
-        match r.poll().ok().unwrap() {
-            Async::Ready(Some(s)) => assert_eq!(
-                s,
-                "Contrary to popular belief, Lorem Ipsum is not simply random text."
-            ),
+        let mut r = req.readlines().require_trailing_newline(true);
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("first\n".to_owned()))
+        );
+        match r.poll().err().unwrap() {
+            ReadlinesError::Incomplete => (),
             _ => unreachable!("error"),
         }
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"first\nlast\n"))
+            .finish();
+        let mut r = req.readlines().require_trailing_newline(true);
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("first\n".to_owned()))
+        );
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("last\n".to_owned()))
+        );
+        assert_eq!(r.poll().ok().unwrap(), Async::Ready(None));
+
+        let mut req = TestRequest::default()
+            .set_payload(Bytes::from_static(b"first\nlast"))
+            .finish();
+        let mut r = req.readlines().require_trailing_newline(false);
+        r.poll().ok().unwrap();
+        assert_eq!(
+            r.poll().ok().unwrap(),
+            Async::Ready(Some("last".to_owned()))
+        );
     }
 }
@@ -1,15 +1,19 @@
+use std::marker::PhantomData;
+use std::time::Instant;
+
 use bytes::BytesMut;
-use futures::{Future, Poll, Stream};
-use http::header::CONTENT_LENGTH;
+use futures::future::MapErr;
+use futures::{Async, Future, Poll, Stream};
 
 use bytes::Bytes;
 use mime;
-use serde::de::DeserializeOwned;
+use serde::de::{DeserializeOwned, Error as DeError};
+use serde::Deserialize;
 use serde_json;
 
 use crate::error::{JsonPayloadError, PayloadError};
-use crate::httpmessage::HttpMessage;
-use crate::payload::Payload;
+use crate::httpmessage::{parse_content_length, HttpMessage};
+use crate::payload::{Deadline, Payload};
 
 /// Request payload json parser that resolves to a deserialized `T` value.
 ///
@@ -46,6 +50,9 @@ pub struct JsonBody<T: HttpMessage, U> {
     limit: usize,
     length: Option<usize>,
     stream: Payload<T::Stream>,
+    empty: Option<U>,
+    strip_bom: bool,
+    deadline: Option<Deadline>,
     err: Option<JsonPayloadError>,
     fut: Option<Box<Future<Item = U, Error = JsonPayloadError>>>,
 }
@@ -64,31 +71,36 @@ where
         } else {
             false
         };
-        if !json {
-            return JsonBody {
-                limit: 262_144,
-                length: None,
-                stream: Payload::None,
-                fut: None,
-                err: Some(JsonPayloadError::ContentType),
-            };
-        }
-
-        let mut len = None;
-        if let Some(l) = req.headers().get(CONTENT_LENGTH) {
-            if let Ok(s) = l.to_str() {
-                if let Ok(l) = s.parse::<usize>() {
-                    len = Some(l)
-                }
+        let fut = JsonBody::err(JsonPayloadError::ContentType);
+        if json {
+            fut.read_from(req)
+        } else {
+            fut
+        }
+    }
+
+    fn read_from(mut self, req: &mut T) -> Self {
+        match parse_content_length(req.headers()) {
+            Ok(len) => {
+                self.length = len;
+                self.stream = req.take_payload();
+                self.err = None;
             }
+            Err(e) => self.err = Some(JsonPayloadError::Payload(e)),
         }
+        self
+    }
 
+    fn err(e: JsonPayloadError) -> Self {
         JsonBody {
             limit: 262_144,
-            length: len,
-            stream: req.take_payload(),
+            length: None,
+            stream: Payload::None,
+            empty: None,
+            strip_bom: false,
+            deadline: None,
             fut: None,
-            err: None,
+            err: Some(e),
         }
     }
 
@@ -97,6 +109,62 @@ where
         self.limit = limit;
         self
     }
+
+    /// Set deadline for reading and parsing the body.
+    ///
+    /// Future fails with `JsonPayloadError::Payload(PayloadError::Timeout)`
+    /// once deadline passes. By default there is no deadline.
+    pub fn deadline(mut self, at: Instant) -> Self {
+        self.deadline = Some(Deadline::new(at));
+        self
+    }
+
+    /// Resolve zero-length body to `U::default()` instead of parse error.
+    pub fn default_on_empty(mut self) -> Self
+    where
+        U: Default,
+    {
+        self.empty = Some(U::default());
+        self
+    }
+
+    /// Strip leading UTF-8 byte order mark (`EF BB BF`) before parsing.
+    ///
+    /// Body without byte order mark is parsed as is. Error positions are
+    /// reported relative to the stripped body. Disabled by default.
+    pub fn strip_bom(mut self, strip: bool) -> Self {
+        self.strip_bom = strip;
+        self
+    }
+
+    /// Parse body regardless of request's content type.
+    ///
+    /// Content type mismatch leaves the payload in the message, so it is
+    /// taken from `req` here. `req` must be the message the future was
+    /// created for.
+    pub fn lenient_content_type(self, req: &mut T) -> Self {
+        match self.err {
+            Some(JsonPayloadError::ContentType) => self.read_from(req),
+            _ => self,
+        }
+    }
+
+    /// Recover the payload taken from the message.
+    ///
+    /// Payload is available only before the future is polled. If the future
+    /// was created in error state (i.e. content type mismatch), payload was
+    /// not taken and remains in the message; `Payload::None` get returned.
+    pub fn into_payload(self) -> Payload<T::Stream> {
+        self.stream
+    }
+
+    /// Map error of the future with `f`.
+    pub fn map_err_with<E, F>(self, f: F) -> MapErr<Self, F>
+    where
+        F: FnOnce(JsonPayloadError) -> E,
+    {
+        self.map_err(f)
+    }
 }
 
 impl<T, U> Future for JsonBody<T, U>
@@ -109,6 +177,12 @@ where
     type Error = JsonPayloadError;
 
     fn poll(&mut self) -> Poll<U, JsonPayloadError> {
+        if let Some(ref mut deadline) = self.deadline {
+            if deadline.expired() {
+                return Err(PayloadError::Timeout.into());
+            }
+        }
+
         if let Some(ref mut fut) = self.fut {
             return fut.poll();
         }
@@ -124,6 +198,8 @@ where
             }
         }
 
+        let empty = self.empty.take();
+        let strip_bom = self.strip_bom;
         let fut = std::mem::replace(&mut self.stream, Payload::None)
             .from_err()
             .fold(BytesMut::with_capacity(8192), move |mut body, chunk| {
@@ -134,12 +210,304 @@ where
                     Ok(body)
                 }
             })
-            .and_then(|body| Ok(serde_json::from_slice::<U>(&body)?));
+            .and_then(move |body| {
+                let body = if strip_bom && body.starts_with(b"\xEF\xBB\xBF") {
+                    &body[3..]
+                } else {
+                    &body[..]
+                };
+                match empty {
+                    Some(value) if body.is_empty() => Ok(value),
+                    _ => Ok(serde_json::from_slice::<U>(body)?),
+                }
+            });
         self.fut = Some(Box::new(fut));
         self.poll()
     }
 }
 
+/// Stream that incrementally parses top-level json array of the request
+/// payload and yields deserialized elements.
+///
+/// Only a single array element is buffered at a time. Returns error:
+///
+/// * content type is not `application/json`
+/// * size of a single element is greater than 256k
+/// * payload is not a json array
+pub struct JsonArrayStream<T: HttpMessage, U> {
+    limit: usize,
+    stream: Payload<T::Stream>,
+    buf: BytesMut,
+    state: ArrayState,
+    eof: bool,
+    err: Option<JsonPayloadError>,
+    _t: PhantomData<U>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ArrayState {
+    Start,
+    First,
+    Element,
+    Next,
+    Done,
+}
+
+impl<T, U> JsonArrayStream<T, U>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    U: DeserializeOwned,
+{
+    /// Create `JsonArrayStream` for request.
+    pub fn new(req: &mut T) -> Self {
+        let json = if let Ok(Some(mime)) = req.mime_type() {
+            mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON)
+        } else {
+            false
+        };
+
+        JsonArrayStream {
+            limit: 262_144,
+            stream: if json {
+                req.take_payload()
+            } else {
+                Payload::None
+            },
+            buf: BytesMut::new(),
+            state: ArrayState::Start,
+            eof: false,
+            err: if json {
+                None
+            } else {
+                Some(JsonPayloadError::ContentType)
+            },
+            _t: PhantomData,
+        }
+    }
+
+    /// Change max size of a single array element. By default max size is 256Kb
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Parse body regardless of request's content type.
+    ///
+    /// Content type mismatch leaves the payload in the message, so it is
+    /// taken from `req` here. `req` must be the message the stream was
+    /// created for.
+    pub fn lenient_content_type(mut self, req: &mut T) -> Self {
+        if let Some(JsonPayloadError::ContentType) = self.err {
+            self.stream = req.take_payload();
+            self.err = None;
+        }
+        self
+    }
+
+    fn next_item(&mut self) -> Result<Option<U>, JsonPayloadError> {
+        loop {
+            match self.buf.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(pos) => {
+                    self.buf.split_to(pos);
+                }
+                None => {
+                    self.buf.clear();
+                    return Ok(None);
+                }
+            }
+
+            let b = self.buf[0];
+            match self.state {
+                ArrayState::Start => {
+                    if b != b'[' {
+                        return Err(JsonPayloadError::Malformed);
+                    }
+                    self.buf.split_to(1);
+                    self.state = ArrayState::First;
+                }
+                ArrayState::First | ArrayState::Next if b == b']' => {
+                    self.buf.split_to(1);
+                    self.state = ArrayState::Done;
+                    return Ok(None);
+                }
+                ArrayState::Next => {
+                    if b != b',' {
+                        return Err(JsonPayloadError::Malformed);
+                    }
+                    self.buf.split_to(1);
+                    self.state = ArrayState::Element;
+                }
+                ArrayState::First | ArrayState::Element => {
+                    return match element_end(&self.buf) {
+                        Some(0) => Err(JsonPayloadError::Malformed),
+                        Some(end) => {
+                            let data = self.buf.split_to(end);
+                            self.state = ArrayState::Next;
+                            Ok(Some(serde_json::from_slice::<U>(&data)?))
+                        }
+                        None => Ok(None),
+                    };
+                }
+                ArrayState::Done => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Find end of a json value that starts at the beginning of `buf`.
+fn element_end(buf: &[u8]) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_str = false;
+    let mut escape = false;
+
+    for (idx, b) in buf.iter().enumerate() {
+        if in_str {
+            if escape {
+                escape = false;
+            } else if *b == b'\\' {
+                escape = true;
+            } else if *b == b'"' {
+                in_str = false;
+                if depth == 0 {
+                    return Some(idx + 1);
+                }
+            }
+            continue;
+        }
+        match *b {
+            b'"' => in_str = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                if depth == 0 {
+                    return Some(idx);
+                }
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx + 1);
+                }
+            }
+            b',' | b' ' | b'\t' | b'\r' | b'\n' if depth == 0 => return Some(idx),
+            _ => (),
+        }
+    }
+    None
+}
+
+impl<T, U> Stream for JsonArrayStream<T, U>
+where
+    T: HttpMessage,
+    T::Stream: Stream<Item = Bytes, Error = PayloadError>,
+    U: DeserializeOwned,
+{
+    type Item = U;
+    type Error = JsonPayloadError;
+
+    fn poll(&mut self) -> Poll<Option<U>, JsonPayloadError> {
+        if let Some(err) = self.err.take() {
+            return Err(err);
+        }
+
+        loop {
+            if let Some(item) = self.next_item()? {
+                return Ok(Async::Ready(Some(item)));
+            }
+            if self.state == ArrayState::Done {
+                return Ok(Async::Ready(None));
+            }
+            if self.eof {
+                return Err(JsonPayloadError::Malformed);
+            }
+            if self.buf.len() > self.limit {
+                return Err(JsonPayloadError::Overflow);
+            }
+
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => self.buf.extend_from_slice(&chunk),
+                Async::Ready(None) => self.eof = true,
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// Content security policy violation report,
+/// see `HttpMessage::csp_report()`
+///
+/// Field names of both `application/csp-report` and Reporting API
+/// (`application/reports+json`) formats are accepted.
+#[derive(Deserialize, Clone, PartialEq, Debug, Default)]
+#[serde(default)]
+pub struct CspReport {
+    /// Uri of the document in which violation occurred
+    #[serde(rename = "document-uri", alias = "documentURL")]
+    pub document_uri: String,
+    /// Referrer of the document
+    pub referrer: Option<String>,
+    /// Directive whose enforcement caused the violation
+    #[serde(rename = "violated-directive")]
+    pub violated_directive: Option<String>,
+    /// Effective directive that was violated
+    #[serde(rename = "effective-directive", alias = "effectiveDirective")]
+    pub effective_directive: Option<String>,
+    /// Original policy as received by the user agent
+    #[serde(rename = "original-policy", alias = "originalPolicy")]
+    pub original_policy: Option<String>,
+    /// Uri of the resource that was blocked
+    #[serde(rename = "blocked-uri", alias = "blockedURL")]
+    pub blocked_uri: Option<String>,
+    /// Uri of the resource where violation occurred
+    #[serde(rename = "source-file", alias = "sourceFile")]
+    pub source_file: Option<String>,
+    /// Line number in `source_file`
+    #[serde(rename = "line-number", alias = "lineNumber")]
+    pub line_number: Option<u32>,
+    /// Column number in `source_file`
+    #[serde(rename = "column-number", alias = "columnNumber")]
+    pub column_number: Option<u32>,
+    /// Http status code of the document
+    #[serde(rename = "status-code", alias = "statusCode")]
+    pub status_code: Option<u16>,
+    /// Either `enforce` or `report`
+    pub disposition: Option<String>,
+}
+
+/// Body of `application/csp-report` request
+#[derive(Deserialize)]
+pub(crate) struct CspReportBody {
+    #[serde(rename = "csp-report")]
+    report: CspReport,
+}
+
+/// Single report of `application/reports+json` request
+#[derive(Deserialize)]
+pub(crate) struct ReportBody {
+    #[serde(rename = "type")]
+    ty: String,
+    body: CspReport,
+}
+
+impl CspReport {
+    pub(crate) fn from_csp_report(body: CspReportBody) -> CspReport {
+        body.report
+    }
+
+    /// Select first `csp-violation` report
+    pub(crate) fn from_reports(
+        reports: Vec<ReportBody>,
+    ) -> Result<CspReport, JsonPayloadError> {
+        reports
+            .into_iter()
+            .find(|report| report.ty == "csp-violation")
+            .map(|report| report.body)
+            .ok_or_else(|| {
+                JsonPayloadError::Deserialize(serde_json::Error::custom(
+                    "no csp-violation report",
+                ))
+            })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use bytes::Bytes;
@@ -148,6 +516,7 @@ mod tests {
     use serde_derive::{Deserialize, Serialize};
 
     use super::*;
+    use crate::payload::PayloadStream;
     use crate::test::TestRequest;
 
     impl PartialEq for JsonPayloadError {
@@ -161,6 +530,10 @@ mod tests {
                     JsonPayloadError::ContentType => true,
                     _ => false,
                 },
+                JsonPayloadError::Malformed => match *other {
+                    JsonPayloadError::Malformed => true,
+                    _ => false,
+                },
                 _ => false,
             }
         }
@@ -199,6 +572,22 @@ mod tests {
         let mut json = req.json::<MyObject>().limit(100);
         assert_eq!(json.poll().err().unwrap(), JsonPayloadError::Overflow);
 
+        let mut req = TestRequest::default()
+            .header(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("application/json"),
+            )
+            .header(
+                header::CONTENT_LENGTH,
+                header::HeaderValue::from_static("+16"),
+            )
+            .set_payload(Bytes::from_static(b"{\"name\": \"test\"}"))
+            .finish();
+        match req.json::<MyObject>().poll().err().unwrap() {
+            JsonPayloadError::Payload(PayloadError::InvalidContentLength) => (),
+            _ => unreachable!("error"),
+        }
+
         let mut req = TestRequest::default()
             .header(
                 header::CONTENT_TYPE,
@@ -219,4 +608,191 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_json_body_deadline() {
+        let mut req = TestRequest::default()
+            .header(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("application/json"),
+            )
+            .set_payload(Bytes::from_static(b"{\"name\": \"test\"}"))
+            .finish();
+        let json = req.json::<MyObject>().deadline(Instant::now());
+        let res = actix_rt::Runtime::new().unwrap().block_on(json);
+        match res.err().unwrap() {
+            JsonPayloadError::Payload(PayloadError::Timeout) => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_json_body_map_err_with() {
+        let mut req = TestRequest::default()
+            .header(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("text/plain"),
+            )
+            .finish();
+        let mut json = req
+            .json::<MyObject>()
+            .map_err_with(|e| format!("json: {}", e));
+        assert_eq!(json.poll().err().unwrap(), "json: Content type error");
+    }
+
+    #[test]
+    fn test_json_body_lenient_content_type() {
+        let mut req = TestRequest::default()
+            .header(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("text/plain"),
+            )
+            .set_payload(Bytes::from_static(b"{\"name\": \"test\"}"))
+            .finish();
+        let mut json = req.json::<MyObject>();
+        match json.poll().err().unwrap() {
+            JsonPayloadError::ContentType => (),
+            _ => unreachable!("error"),
+        }
+        match json.into_payload() {
+            Payload::None => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut json = req.json::<MyObject>().lenient_content_type(&mut req);
+        assert_eq!(
+            json.poll().ok().unwrap(),
+            Async::Ready(MyObject {
+                name: "test".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_json_body_strip_bom() {
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "application/json")
+            .set_payload(Bytes::from_static(b"\xEF\xBB\xBF{\"name\": \"test\"}"))
+            .finish();
+        let mut json = req.json::<MyObject>().strip_bom(true);
+        assert_eq!(
+            json.poll().ok().unwrap(),
+            Async::Ready(MyObject {
+                name: "test".to_owned()
+            })
+        );
+
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "application/json")
+            .set_payload(Bytes::from_static(b"{\"name\": \"test\"}"))
+            .finish();
+        let mut json = req.json::<MyObject>().strip_bom(true);
+        assert_eq!(
+            json.poll().ok().unwrap(),
+            Async::Ready(MyObject {
+                name: "test".to_owned()
+            })
+        );
+
+        let mut req = TestRequest::with_header(header::CONTENT_TYPE, "application/json")
+            .set_payload(Bytes::from_static(b"\xEF\xBB\xBF{\"name\": \"test\"}"))
+            .finish();
+        let mut json = req.json::<MyObject>();
+        match json.poll().err().unwrap() {
+            JsonPayloadError::Deserialize(_) => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[derive(Deserialize, PartialEq, Debug, Default)]
+    struct Options {
+        verbose: bool,
+    }
+
+    #[test]
+    fn test_json_body_default_on_empty() {
+        let mut req = TestRequest::default()
+            .header(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("application/json"),
+            )
+            .finish();
+        let mut json = req.json::<Options>().default_on_empty();
+        assert_eq!(json.poll().ok().unwrap(), Async::Ready(Options::default()));
+
+        let mut req = TestRequest::default()
+            .header(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("application/json"),
+            )
+            .finish();
+        let mut json = req.json::<Options>();
+        match json.poll().err().unwrap() {
+            JsonPayloadError::Deserialize(_) => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut req = TestRequest::default()
+            .header(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("application/json"),
+            )
+            .set_payload(Bytes::from_static(b"{\"verbose\": true}"))
+            .finish();
+        let mut json = req.json::<Options>().default_on_empty();
+        assert_eq!(
+            json.poll().ok().unwrap(),
+            Async::Ready(Options { verbose: true })
+        );
+    }
+
+    fn json_stream_request(chunks: Vec<&'static [u8]>) -> crate::Request {
+        let stream: PayloadStream =
+            Box::new(futures::stream::iter_ok::<_, PayloadError>(
+                chunks.into_iter().map(Bytes::from_static),
+            ));
+        let mut req = crate::Request::with_payload(Payload::Stream(stream));
+        req.headers_mut().insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        req
+    }
+
+    #[test]
+    fn test_json_array_stream() {
+        let mut req = json_stream_request(vec![
+            b"[ {\"name\": \"fi",
+            b"rst\"}, {\"name\": \"[s,e}c\\\"ond\"}",
+            b" ]",
+        ]);
+        let mut stream = req.json_array_stream::<MyObject>();
+        assert_eq!(
+            stream.poll().ok().unwrap(),
+            Async::Ready(Some(MyObject {
+                name: "first".to_owned()
+            }))
+        );
+        assert_eq!(
+            stream.poll().ok().unwrap(),
+            Async::Ready(Some(MyObject {
+                name: "[s,e}c\"ond".to_owned()
+            }))
+        );
+        assert_eq!(stream.poll().ok().unwrap(), Async::Ready(None));
+
+        let mut req = json_stream_request(vec![b"[1, 2", b"3 ,4]"]);
+        let mut stream = req.json_array_stream::<u32>();
+        assert_eq!(stream.poll().ok().unwrap(), Async::Ready(Some(1)));
+        assert_eq!(stream.poll().ok().unwrap(), Async::Ready(Some(23)));
+        assert_eq!(stream.poll().ok().unwrap(), Async::Ready(Some(4)));
+        assert_eq!(stream.poll().ok().unwrap(), Async::Ready(None));
+
+        let mut req = json_stream_request(vec![b"[1, 2"]);
+        let mut stream = req.json_array_stream::<u32>();
+        assert_eq!(stream.poll().ok().unwrap(), Async::Ready(Some(1)));
+        assert_eq!(stream.poll().err().unwrap(), JsonPayloadError::Malformed);
+
+        let mut req = json_stream_request(vec![b"{\"name\": \"test\"}"]);
+        let mut stream = req.json_array_stream::<MyObject>();
+        assert_eq!(stream.poll().err().unwrap(), JsonPayloadError::Malformed);
+    }
 }
@@ -112,8 +112,16 @@ pub mod dev {
     //! use actix_http::dev::*;
     //! ```
 
-    pub use crate::httpmessage::{MessageBody, Readlines, UrlEncoded};
-    pub use crate::json::JsonBody;
+    pub use crate::httpmessage::{
+        BodyReader, BodySegments, BodyStats, BodyUntil, CsvStream, DedupPolicy,
+        FormField, HashAlgo, HmacAlgo, LengthPrefix, LengthPrefixedFrames, MessageBody,
+        Negotiation, RangeDecision, Readlines, SplitBody, SseEvent, SseEvents,
+        UrlEncoded, Utf8Chunks,
+    };
+    pub use crate::json::{CspReport, JsonArrayStream, JsonBody};
+    pub use crate::payload::{
+        Deadline, Dechunk, Decoder, InactivityTimeout, Limited, MinRate, OnStall,
+    };
     pub use crate::response::ResponseBuilder;
 }
 
@@ -1,8 +1,19 @@
-use bytes::Bytes;
+use std::io;
+use std::time::{Duration, Instant};
+
+use actix_codec::Decoder as CodecDecoder;
+use byteorder::{ByteOrder, LittleEndian};
+use bytes::{Bytes, BytesMut};
+use flate2::{Crc, Decompress, FlushDecompress, Status};
+use futures::stream::FromErr;
 use futures::{Async, Poll, Stream};
 use h2::RecvStream;
+use http::{header, HeaderMap};
+use tokio_timer::Delay;
 
 use crate::error::PayloadError;
+use crate::h1::{PayloadDecoder, PayloadItem};
+use crate::header::ContentEncoding;
 
 /// Type represent boxed payload
 pub type PayloadStream = Box<dyn Stream<Item = Bytes, Error = PayloadError>>;
@@ -46,6 +57,60 @@ impl<S> Payload<S> {
     }
 }
 
+impl<S> Payload<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Decode chunked transfer encoding of the payload.
+    ///
+    /// Use it for payloads that still contain raw chunked body,
+    /// payloads produced by the http/1 dispatcher are already decoded.
+    pub fn dechunk(self) -> Dechunk<Self> {
+        Dechunk::new(self)
+    }
+
+    /// Require payload to be received at least `bytes_per_sec` rate.
+    ///
+    /// See `MinRate` for details.
+    pub fn min_rate(self, bytes_per_sec: usize) -> MinRate<Self> {
+        MinRate::new(self, bytes_per_sec)
+    }
+
+    /// Fail if no data received within `timeout` period.
+    ///
+    /// See `InactivityTimeout` for details.
+    pub fn inactivity_timeout(self, timeout: Duration) -> InactivityTimeout<Self> {
+        InactivityTimeout::new(self, timeout)
+    }
+
+    /// Fail as soon as payload size exceeds `max` bytes.
+    ///
+    /// See `Limited` for details.
+    pub fn limited(self, max: usize) -> Limited<Self> {
+        Limited::new(self, max)
+    }
+
+    /// Decompress payload with specified content coding.
+    ///
+    /// See `Decoder` for details.
+    pub fn decode(self, encoding: ContentEncoding) -> Decoder<Self> {
+        Decoder::new(self, encoding)
+    }
+
+    /// Convert payload errors to `io::Error`.
+    ///
+    /// Errors are mapped to `io::ErrorKind` as follows:
+    ///
+    /// * `Incomplete` with io error - original io error
+    /// * `Incomplete` without io error - `UnexpectedEof`
+    /// * `TooSlow`, `Timeout` - `TimedOut`
+    /// * `Http2Payload` - `Other`
+    /// * all other errors, i.e. `Overflow` - `InvalidData`
+    pub fn into_io(self) -> FromErr<Self, io::Error> {
+        self.from_err()
+    }
+}
+
 impl<S> Stream for Payload<S>
 where
     S: Stream<Item = Bytes, Error = PayloadError>,
@@ -62,3 +127,939 @@ where
         }
     }
 }
+
+/// Stream that decodes chunked transfer encoding.
+///
+/// Stream ends at zero-length terminating chunk. Malformed chunk is reported
+/// as `PayloadError::EncodingCorrupted`.
+pub struct Dechunk<S> {
+    stream: S,
+    buf: BytesMut,
+    decoder: PayloadDecoder,
+    eof: bool,
+}
+
+impl<S> Dechunk<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create new chunked transfer encoding decoder
+    pub fn new(stream: S) -> Dechunk<S> {
+        Dechunk {
+            stream,
+            buf: BytesMut::new(),
+            decoder: PayloadDecoder::chunked(),
+            eof: false,
+        }
+    }
+}
+
+impl<S> Stream for Dechunk<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        loop {
+            if self.eof {
+                return Ok(Async::Ready(None));
+            }
+
+            match self
+                .decoder
+                .decode(&mut self.buf)
+                .map_err(|_| PayloadError::EncodingCorrupted)?
+            {
+                Some(PayloadItem::Chunk(chunk)) => return Ok(Async::Ready(Some(chunk))),
+                Some(PayloadItem::Eof) => {
+                    self.eof = true;
+                    return Ok(Async::Ready(None));
+                }
+                None => (),
+            }
+
+            match self.stream.poll()? {
+                Async::Ready(Some(data)) => self.buf.extend_from_slice(&data),
+                Async::Ready(None) => return Err(PayloadError::Incomplete(None)),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// Stream that enforces minimal receive rate of the payload.
+///
+/// After initial grace period (5 seconds by default) total amount of
+/// received bytes must keep up with `bytes_per_sec` rate, otherwise stream
+/// fails with `PayloadError::TooSlow`. This protects server from clients
+/// that send body very slowly (slow-loris attack).
+///
+/// Stream uses `tokio-timer`, so it must be polled within actix
+/// (or tokio) runtime.
+pub struct MinRate<S> {
+    stream: S,
+    rate: usize,
+    grace: Duration,
+    start: Instant,
+    received: usize,
+    timer: Option<Delay>,
+}
+
+impl<S> MinRate<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create new minimal rate stream. Zero rate disables the check.
+    pub fn new(stream: S, bytes_per_sec: usize) -> MinRate<S> {
+        MinRate {
+            stream,
+            rate: bytes_per_sec,
+            grace: Duration::from_secs(5),
+            start: Instant::now(),
+            received: 0,
+            timer: None,
+        }
+    }
+
+    /// Set initial period during which rate is not checked.
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    fn deadline(&self) -> Instant {
+        let millis = (self.received as u64).saturating_mul(1000) / self.rate as u64;
+        self.start + self.grace + Duration::from_millis(millis)
+    }
+}
+
+impl<S> Stream for MinRate<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self.stream.poll()? {
+            Async::Ready(Some(chunk)) => {
+                self.received += chunk.len();
+                Ok(Async::Ready(Some(chunk)))
+            }
+            Async::Ready(None) => Ok(Async::Ready(None)),
+            Async::NotReady => {
+                if self.rate == 0 {
+                    return Ok(Async::NotReady);
+                }
+
+                let deadline = self.deadline();
+                match self.timer {
+                    Some(ref mut timer) => {
+                        if timer.deadline() != deadline {
+                            timer.reset(deadline);
+                        }
+                    }
+                    None => self.timer = Some(Delay::new(deadline)),
+                }
+
+                match self.timer.as_mut().unwrap().poll() {
+                    Ok(Async::Ready(_)) => Err(PayloadError::TooSlow),
+                    Ok(Async::NotReady) => Ok(Async::NotReady),
+                    Err(e) => Err(io::Error::new(io::ErrorKind::Other, e).into()),
+                }
+            }
+        }
+    }
+}
+
+/// Stream that fails with `PayloadError::Overflow` as soon as total size
+/// of received chunks exceeds the limit.
+///
+/// Chunks are passed through as they arrive, chunk that crosses the limit
+/// is not yielded.
+pub struct Limited<S> {
+    stream: S,
+    limit: usize,
+    size: usize,
+}
+
+impl<S> Limited<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create new size limited stream
+    pub fn new(stream: S, limit: usize) -> Limited<S> {
+        Limited {
+            stream,
+            limit,
+            size: 0,
+        }
+    }
+}
+
+impl<S> Stream for Limited<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self.stream.poll()? {
+            Async::Ready(Some(chunk)) => {
+                self.size += chunk.len();
+                if self.size > self.limit {
+                    Err(PayloadError::Overflow)
+                } else {
+                    Ok(Async::Ready(Some(chunk)))
+                }
+            }
+            res => Ok(res),
+        }
+    }
+}
+
+/// Wall-clock deadline shared by payload collection and parsing.
+///
+/// Deadline uses `tokio-timer`, so it must be polled within actix
+/// (or tokio) runtime.
+pub struct Deadline(Delay);
+
+impl Deadline {
+    /// Create deadline that expires at `at` instant.
+    pub fn new(at: Instant) -> Deadline {
+        Deadline(Delay::new(at))
+    }
+
+    /// Check if deadline is passed.
+    ///
+    /// If it is not, current task is notified once deadline passes.
+    /// Timer error is treated as expired deadline.
+    pub fn expired(&mut self) -> bool {
+        match self.0.poll() {
+            Ok(Async::NotReady) => false,
+            Ok(Async::Ready(_)) | Err(_) => true,
+        }
+    }
+}
+
+/// Stream that fails with `PayloadError::Timeout` if no chunk is received
+/// within configured period. Timer restarts on every received chunk.
+///
+/// Stream uses `tokio-timer`, so it must be polled within actix
+/// (or tokio) runtime.
+pub struct InactivityTimeout<S> {
+    stream: S,
+    timeout: Duration,
+    timer: Option<Delay>,
+}
+
+impl<S> InactivityTimeout<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create new inactivity timeout stream
+    pub fn new(stream: S, timeout: Duration) -> InactivityTimeout<S> {
+        InactivityTimeout {
+            stream,
+            timeout,
+            timer: None,
+        }
+    }
+}
+
+impl<S> Stream for InactivityTimeout<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self.stream.poll()? {
+            Async::Ready(Some(chunk)) => {
+                // restart timer on next pending poll
+                self.timer = None;
+                Ok(Async::Ready(Some(chunk)))
+            }
+            Async::Ready(None) => Ok(Async::Ready(None)),
+            Async::NotReady => {
+                if self.timer.is_none() {
+                    self.timer = Some(Delay::new(Instant::now() + self.timeout));
+                }
+                match self.timer.as_mut().unwrap().poll() {
+                    Ok(Async::Ready(_)) => Err(PayloadError::Timeout),
+                    Ok(Async::NotReady) => Ok(Async::NotReady),
+                    Err(e) => Err(io::Error::new(io::ErrorKind::Other, e).into()),
+                }
+            }
+        }
+    }
+}
+
+/// Stream that calls a callback each time no chunk is received within
+/// configured period.
+///
+/// Unlike `InactivityTimeout` stream is not aborted, callback is called
+/// again after every further period without data. Timer restarts on every
+/// received chunk.
+///
+/// Stream uses `tokio-timer`, so it must be polled within actix
+/// (or tokio) runtime.
+pub struct OnStall<S, F> {
+    stream: S,
+    period: Duration,
+    f: F,
+    timer: Option<Delay>,
+}
+
+impl<S, F> OnStall<S, F>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+    F: FnMut(),
+{
+    /// Create new stall notification stream
+    pub fn new(stream: S, period: Duration, f: F) -> OnStall<S, F> {
+        OnStall {
+            stream,
+            period,
+            f,
+            timer: None,
+        }
+    }
+}
+
+impl<S, F> Stream for OnStall<S, F>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+    F: FnMut(),
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self.stream.poll()? {
+            Async::Ready(Some(chunk)) => {
+                // restart timer on next pending poll
+                self.timer = None;
+                Ok(Async::Ready(Some(chunk)))
+            }
+            Async::Ready(None) => Ok(Async::Ready(None)),
+            Async::NotReady => loop {
+                if self.timer.is_none() {
+                    self.timer = Some(Delay::new(Instant::now() + self.period));
+                }
+                match self.timer.as_mut().unwrap().poll() {
+                    Ok(Async::Ready(_)) => {
+                        (self.f)();
+                        let next = Instant::now() + self.period;
+                        self.timer.as_mut().unwrap().reset(next);
+                    }
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e).into()),
+                }
+            },
+        }
+    }
+}
+
+/// Max size of decompressed data produced by single inflate step
+const INFLATE_WINDOW: usize = 16_384;
+
+/// Max size of gzip member header (including extra field, name and comment)
+const MAX_GZIP_HEADER: usize = 65_536;
+
+/// Get content encoding specified by *Content-Encoding* header.
+///
+/// Returns `None` if header contains unsupported content coding.
+pub(crate) fn content_encoding(headers: &HeaderMap) -> Option<ContentEncoding> {
+    let value = match headers.get(header::CONTENT_ENCODING) {
+        Some(value) => value.to_str().ok()?.trim(),
+        None => return Some(ContentEncoding::Identity),
+    };
+    match ContentEncoding::from(value) {
+        ContentEncoding::Identity
+            if !value.is_empty() && !value.eq_ignore_ascii_case("identity") =>
+        {
+            None
+        }
+        encoding => Some(encoding),
+    }
+}
+
+/// Stream that decompresses payload chunks according to content encoding.
+///
+/// Data is inflated in bounded steps, each step produces at most 16Kb
+/// of decompressed data. Compression ratio and size limit are checked
+/// after every step.
+///
+/// Corrupted input is reported as `PayloadError::EncodingCorrupted`.
+pub struct Decoder<S> {
+    stream: S,
+    decoder: Option<ContentDecoder>,
+    buf: Bytes,
+    eof: bool,
+    err: Option<PayloadError>,
+    max_ratio: Option<f64>,
+    limit: Option<usize>,
+    read: usize,
+    written: usize,
+}
+
+impl<S> Decoder<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    /// Create decoder for specified content encoding.
+    ///
+    /// Payload is passed through as is for `identity` encoding.
+    pub fn new(stream: S, encoding: ContentEncoding) -> Decoder<S> {
+        let decoder = match encoding {
+            ContentEncoding::Br => Some(ContentDecoder::Br(
+                Box::new(brotli2::raw::Decompress::new()),
+                false,
+            )),
+            ContentEncoding::Deflate => {
+                Some(ContentDecoder::Deflate(Decompress::new(true), false))
+            }
+            ContentEncoding::Gzip => Some(ContentDecoder::Gzip(GzipState::new())),
+            _ => None,
+        };
+        Decoder {
+            stream,
+            decoder,
+            buf: Bytes::new(),
+            eof: false,
+            err: None,
+            max_ratio: None,
+            limit: None,
+            read: 0,
+            written: 0,
+        }
+    }
+
+    /// Create decoder for encoding specified by *Content-Encoding* header.
+    ///
+    /// Stream fails with `PayloadError::EncodingCorrupted` if header
+    /// contains unsupported content coding.
+    pub fn from_headers(stream: S, headers: &HeaderMap) -> Decoder<S> {
+        match content_encoding(headers) {
+            Some(encoding) => Decoder::new(stream, encoding),
+            None => {
+                let mut decoder = Decoder::new(stream, ContentEncoding::Identity);
+                decoder.err = Some(PayloadError::EncodingCorrupted);
+                decoder
+            }
+        }
+    }
+
+    /// Set max allowed ratio between decompressed and compressed sizes.
+    ///
+    /// Stream fails with `PayloadError::Overflow` as soon as decompressed
+    /// output exceeds `ratio * compressed input`.
+    pub fn max_ratio(mut self, ratio: f64) -> Self {
+        self.max_ratio = Some(ratio);
+        self
+    }
+
+    /// Set max size of decompressed data.
+    ///
+    /// Stream fails with `PayloadError::Overflow` as soon as decompressed
+    /// output exceeds the limit.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn check_ratio(&mut self, chunk: &Bytes) -> Result<(), PayloadError> {
+        self.written += chunk.len();
+        if let Some(ratio) = self.max_ratio {
+            if self.written as f64 > ratio * self.read as f64 {
+                return Err(PayloadError::Overflow);
+            }
+        }
+        if let Some(limit) = self.limit {
+            if self.written > limit {
+                return Err(PayloadError::Overflow);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S> Stream for Decoder<S>
+where
+    S: Stream<Item = Bytes, Error = PayloadError>,
+{
+    type Item = Bytes;
+    type Error = PayloadError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if let Some(err) = self.err.take() {
+            return Err(err);
+        }
+        if self.decoder.is_none() {
+            return self.stream.poll();
+        }
+
+        loop {
+            // inflater may hold decompressed data even if input is consumed
+            let chunk = self.decoder.as_mut().unwrap().decode(&mut self.buf)?;
+            if let Some(chunk) = chunk {
+                self.check_ratio(&chunk)?;
+                return Ok(Async::Ready(Some(chunk)));
+            }
+            if self.eof {
+                return if self.decoder.as_ref().unwrap().is_finished() {
+                    Ok(Async::Ready(None))
+                } else {
+                    Err(PayloadError::EncodingCorrupted)
+                };
+            }
+
+            match self.stream.poll()? {
+                Async::Ready(Some(chunk)) => {
+                    self.read += chunk.len();
+                    if self.buf.is_empty() {
+                        self.buf = chunk;
+                    } else {
+                        self.buf.extend_from_slice(&chunk);
+                    }
+                }
+                Async::Ready(None) => self.eof = true,
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+enum ContentDecoder {
+    Deflate(Decompress, bool),
+    Gzip(GzipState),
+    Br(Box<brotli2::raw::Decompress>, bool),
+}
+
+impl ContentDecoder {
+    fn is_finished(&self) -> bool {
+        match self {
+            ContentDecoder::Deflate(_, finished) | ContentDecoder::Br(_, finished) => {
+                *finished
+            }
+            ContentDecoder::Gzip(ref state) => state.finished,
+        }
+    }
+
+    /// Decompress next portion of input.
+    ///
+    /// Returns `None` if more input is required.
+    fn decode(&mut self, input: &mut Bytes) -> Result<Option<Bytes>, PayloadError> {
+        if self.is_finished() {
+            if !input.is_empty() {
+                return Err(PayloadError::EncodingCorrupted);
+            }
+            return Ok(None);
+        }
+
+        let chunk = match self {
+            ContentDecoder::Deflate(ref mut decoder, ref mut finished) => {
+                let (chunk, end) = inflate(decoder, input)?;
+                *finished = end;
+                chunk
+            }
+            ContentDecoder::Gzip(ref mut state) => state.decode(input)?,
+            ContentDecoder::Br(ref mut decoder, ref mut finished) => {
+                let mut buf = vec![0; INFLATE_WINDOW];
+                let (status, consumed, produced) = {
+                    let mut inp: &[u8] = &input[..];
+                    let mut out: &mut [u8] = &mut buf;
+                    let status = decoder
+                        .decompress(&mut inp, &mut out)
+                        .map_err(|_| PayloadError::EncodingCorrupted)?;
+                    (status, input.len() - inp.len(), INFLATE_WINDOW - out.len())
+                };
+                input.split_to(consumed);
+                if let brotli2::raw::DeStatus::Finished = status {
+                    *finished = true;
+                }
+                buf.truncate(produced);
+                buf
+            }
+        };
+        if chunk.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(Bytes::from(chunk)))
+        }
+    }
+}
+
+/// Inflate at most `INFLATE_WINDOW` bytes from input.
+///
+/// Returns decompressed data and flag indicating end of deflate stream.
+fn inflate(
+    decoder: &mut Decompress,
+    input: &mut Bytes,
+) -> Result<(Vec<u8>, bool), PayloadError> {
+    let mut buf = Vec::with_capacity(INFLATE_WINDOW);
+    let total_in = decoder.total_in();
+    let status = decoder
+        .decompress_vec(&input[..], &mut buf, FlushDecompress::None)
+        .map_err(|_| PayloadError::EncodingCorrupted)?;
+    let consumed = (decoder.total_in() - total_in) as usize;
+    if consumed == 0
+        && buf.is_empty()
+        && !input.is_empty()
+        && status != Status::StreamEnd
+    {
+        return Err(PayloadError::EncodingCorrupted);
+    }
+    input.split_to(consumed);
+    Ok((buf, status == Status::StreamEnd))
+}
+
+/// Gzip member decoding state
+struct GzipState {
+    header: Option<BytesMut>,
+    decoder: Decompress,
+    crc: Crc,
+    trailer: BytesMut,
+    inflated: bool,
+    finished: bool,
+}
+
+impl GzipState {
+    fn new() -> GzipState {
+        GzipState {
+            header: Some(BytesMut::new()),
+            decoder: Decompress::new(false),
+            crc: Crc::new(),
+            trailer: BytesMut::with_capacity(8),
+            inflated: false,
+            finished: false,
+        }
+    }
+
+    fn decode(&mut self, input: &mut Bytes) -> Result<Vec<u8>, PayloadError> {
+        if let Some(mut header) = self.header.take() {
+            header.extend_from_slice(&input);
+            match gzip_header_len(&header)? {
+                Some(len) => *input = header.split_off(len).freeze(),
+                None => {
+                    if header.len() > MAX_GZIP_HEADER {
+                        return Err(PayloadError::EncodingCorrupted);
+                    }
+                    *input = Bytes::new();
+                    self.header = Some(header);
+                    return Ok(Vec::new());
+                }
+            }
+        }
+
+        let mut chunk = Vec::new();
+        if !self.inflated {
+            let (buf, end) = inflate(&mut self.decoder, input)?;
+            self.crc.update(&buf);
+            self.inflated = end;
+            chunk = buf;
+        }
+
+        if self.inflated && !self.finished {
+            let len = (8 - self.trailer.len()).min(input.len());
+            self.trailer.extend_from_slice(&input.split_to(len));
+            if self.trailer.len() == 8 {
+                if LittleEndian::read_u32(&self.trailer[..4]) != self.crc.sum()
+                    || LittleEndian::read_u32(&self.trailer[4..]) != self.crc.amount()
+                {
+                    return Err(PayloadError::EncodingCorrupted);
+                }
+                self.finished = true;
+            }
+        }
+        if self.finished && !input.is_empty() {
+            return Err(PayloadError::EncodingCorrupted);
+        }
+        Ok(chunk)
+    }
+}
+
+/// Get length of gzip member header, `None` if header is incomplete.
+fn gzip_header_len(buf: &[u8]) -> Result<Option<usize>, PayloadError> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if buf.len() < 10 {
+        return Ok(None);
+    }
+    if buf[0] != 0x1f || buf[1] != 0x8b || buf[2] != 8 || buf[3] & 0xe0 != 0 {
+        return Err(PayloadError::EncodingCorrupted);
+    }
+    let flags = buf[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        if buf.len() < pos + 2 {
+            return Ok(None);
+        }
+        pos += 2 + LittleEndian::read_u16(&buf[pos..]) as usize;
+    }
+    for flag in &[FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            if pos > buf.len() {
+                return Ok(None);
+            }
+            match buf[pos..].iter().position(|b| *b == 0) {
+                Some(idx) => pos += idx + 1,
+                None => return Ok(None),
+            }
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    if pos > buf.len() {
+        Ok(None)
+    } else {
+        Ok(Some(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_rt::Runtime;
+    use futures::Future;
+
+    fn payload(data: &'static [u8]) -> Payload {
+        payload_from(Bytes::from_static(data))
+    }
+
+    fn payload_from(data: Bytes) -> Payload {
+        let mut pl = crate::h1::Payload::empty();
+        pl.unread_data(data);
+        pl.into()
+    }
+
+    #[test]
+    fn test_dechunk() {
+        let mut pl = payload(b"4\r\ndata\r\n4\r\nline\r\n0\r\n\r\n").dechunk();
+        assert_eq!(
+            pl.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::from_static(b"data")))
+        );
+        assert_eq!(
+            pl.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::from_static(b"line")))
+        );
+        assert_eq!(pl.poll().ok().unwrap(), Async::Ready(None));
+
+        let mut pl = payload(b"x\r\ndata\r\n").dechunk();
+        match pl.poll().err().unwrap() {
+            PayloadError::EncodingCorrupted => (),
+            _ => unreachable!("error"),
+        }
+
+        let mut pl = payload(b"4\r\ndata\r\n").dechunk();
+        pl.poll().ok().unwrap();
+        match pl.poll().err().unwrap() {
+            PayloadError::Incomplete(None) => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(b"data").unwrap();
+        let mut pl = crate::h1::Payload::empty();
+        pl.unread_data(Bytes::from(e.finish().unwrap()));
+        let pl: Payload = pl.into();
+        let res = pl.decode(ContentEncoding::Gzip).concat2().wait();
+        assert_eq!(res.ok().unwrap(), Bytes::from_static(b"data"));
+
+        let res = payload(b"not gzip")
+            .decode(ContentEncoding::Gzip)
+            .concat2()
+            .wait();
+        match res.err().unwrap() {
+            PayloadError::EncodingCorrupted => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_decode_bounded() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut e = GzEncoder::new(Vec::new(), Compression::default());
+        e.write_all(&vec![b'a'; 1_000_000]).unwrap();
+        let enc = Bytes::from(e.finish().unwrap());
+
+        let chunks = Decoder::new(payload_from(enc.clone()), ContentEncoding::Gzip)
+            .collect()
+            .wait()
+            .unwrap();
+        assert!(chunks.iter().all(|chunk| chunk.len() <= INFLATE_WINDOW));
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.len()).sum::<usize>(),
+            1_000_000
+        );
+
+        let mut decoder =
+            Decoder::new(payload_from(enc.clone()), ContentEncoding::Gzip).limit(1000);
+        match decoder.poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        let truncated = enc.slice_to(enc.len() - 4);
+        let res = Decoder::new(payload_from(truncated), ContentEncoding::Gzip)
+            .concat2()
+            .wait();
+        match res.err().unwrap() {
+            PayloadError::EncodingCorrupted => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_decode_unsupported() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_ENCODING,
+            header::HeaderValue::from_static("compress"),
+        );
+        let res = Decoder::from_headers(payload(b"data"), &headers)
+            .concat2()
+            .wait();
+        match res.err().unwrap() {
+            PayloadError::EncodingCorrupted => (),
+            _ => unreachable!("error"),
+        }
+
+        headers.insert(
+            header::CONTENT_ENCODING,
+            header::HeaderValue::from_static("identity"),
+        );
+        let res = Decoder::from_headers(payload(b"data"), &headers)
+            .concat2()
+            .wait();
+        assert_eq!(res.ok().unwrap(), Bytes::from_static(b"data"));
+    }
+
+    #[test]
+    fn test_into_io() {
+        let res = payload(b"data").into_io().concat2().wait();
+        assert_eq!(res.ok().unwrap(), Bytes::from_static(b"data"));
+
+        let stream: PayloadStream = Box::new(futures::stream::iter_result(vec![
+            Ok(Bytes::from_static(b"data")),
+            Err(PayloadError::Overflow),
+        ]));
+        let res = Payload::Stream(stream).into_io().concat2().wait();
+        assert_eq!(res.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_limited() {
+        let stream: PayloadStream =
+            Box::new(futures::stream::iter_ok::<_, PayloadError>(vec![
+                Bytes::from_static(b"data"),
+                Bytes::from_static(b"line"),
+            ]));
+        let mut pl = Payload::Stream(stream).limited(6);
+        assert_eq!(
+            pl.poll().ok().unwrap(),
+            Async::Ready(Some(Bytes::from_static(b"data")))
+        );
+        match pl.poll().err().unwrap() {
+            PayloadError::Overflow => (),
+            _ => unreachable!("error"),
+        }
+
+        let res = payload(b"data").limited(4).concat2().wait();
+        assert_eq!(res.ok().unwrap(), Bytes::from_static(b"data"));
+    }
+
+    #[test]
+    fn test_min_rate() {
+        let (_sender, pl) = crate::h1::Payload::create(false);
+        let pl: Payload = pl.into();
+        let res = Runtime::new()
+            .unwrap()
+            .block_on(pl.min_rate(1024).grace(Duration::from_millis(10)).concat2());
+        match res.err().unwrap() {
+            PayloadError::TooSlow => (),
+            _ => unreachable!("error"),
+        }
+
+        let res = Runtime::new()
+            .unwrap()
+            .block_on(payload(b"data").min_rate(1024).concat2());
+        assert_eq!(res.ok().unwrap(), Bytes::from_static(b"data"));
+    }
+
+    #[test]
+    fn test_on_stall() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let stalls = Rc::new(Cell::new(0));
+        let counter = stalls.clone();
+        let stream = Delay::new(Instant::now() + Duration::from_millis(50))
+            .map(|_| Bytes::from_static(b"data"))
+            .map_err(|_| PayloadError::Timeout)
+            .into_stream();
+        let res = Runtime::new().unwrap().block_on(
+            OnStall::new(stream, Duration::from_millis(10), move || {
+                counter.set(counter.get() + 1)
+            })
+            .concat2(),
+        );
+        assert_eq!(res.ok().unwrap(), Bytes::from_static(b"data"));
+        assert!(stalls.get() >= 2);
+    }
+
+    #[test]
+    fn test_deadline() {
+        Runtime::new()
+            .unwrap()
+            .block_on(futures::future::lazy(|| {
+                let mut deadline =
+                    Deadline::new(Instant::now() + Duration::from_secs(60));
+                assert!(!deadline.expired());
+                let mut deadline = Deadline::new(Instant::now());
+                assert!(deadline.expired());
+                Ok::<_, ()>(())
+            }))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_inactivity_timeout() {
+        let (_sender, pl) = crate::h1::Payload::create(false);
+        let pl: Payload = pl.into();
+        let res = Runtime::new()
+            .unwrap()
+            .block_on(pl.inactivity_timeout(Duration::from_millis(10)).concat2());
+        match res.err().unwrap() {
+            PayloadError::Timeout => (),
+            _ => unreachable!("error"),
+        }
+
+        let res = Runtime::new().unwrap().block_on(
+            payload(b"data")
+                .inactivity_timeout(Duration::from_millis(10))
+                .concat2(),
+        );
+        assert_eq!(res.ok().unwrap(), Bytes::from_static(b"data"));
+    }
+}
@@ -1,8 +1,18 @@
 use std::cell::{Ref, RefMut};
+use std::collections::HashMap;
 use std::fmt;
 
+use bytes::Bytes;
+use futures::future::{err, result};
+use futures::{Future, Stream};
+use http::header::HeaderName;
 use http::{header, HeaderMap, Method, Uri, Version};
+use serde::de::DeserializeOwned;
+use serde_urlencoded;
+use sha2::{Digest, Sha256};
+use url::form_urlencoded;
 
+use crate::error::{ParseError, PayloadError, UrlencodedError};
 use crate::extensions::Extensions;
 use crate::httpmessage::HttpMessage;
 use crate::message::{Message, RequestHead};
@@ -37,6 +47,25 @@ impl<P> HttpMessage for Request<P> {
     fn take_payload(&mut self) -> Payload<P> {
         std::mem::replace(&mut self.payload, Payload::None)
     }
+
+    #[inline]
+    fn http_version(&self) -> Option<Version> {
+        Some(self.version())
+    }
+
+    /// Request is retry-safe if its method is idempotent
+    /// or it carries *Idempotency-Key* header.
+    fn is_retry_safe(&self) -> bool {
+        match *self.method() {
+            Method::GET
+            | Method::HEAD
+            | Method::OPTIONS
+            | Method::TRACE
+            | Method::PUT
+            | Method::DELETE => true,
+            _ => self.idempotency_key().is_some(),
+        }
+    }
 }
 
 impl From<Message<RequestHead>> for Request<PayloadStream> {
@@ -137,6 +166,147 @@ impl<P> Request<P> {
         self.head().uri.path()
     }
 
+    /// The query string of this Request.
+    ///
+    /// Empty string get returned if request uri does not contain query.
+    #[inline]
+    pub fn query_string(&self) -> &str {
+        self.head().uri.query().unwrap_or("")
+    }
+
+    /// Deserialize request's query string.
+    pub fn query<T: DeserializeOwned>(&self) -> Result<T, UrlencodedError> {
+        serde_urlencoded::from_str::<T>(self.query_string())
+            .map_err(|_| UrlencodedError::Parse)
+    }
+
+    /// Parse request's query string into a map of values.
+    ///
+    /// Repeated parameters are collected in order of appearance,
+    /// i.e. `a=1&a=2` results in `{"a": ["1", "2"]}`.
+    pub fn query_multi(&self) -> Result<HashMap<String, Vec<String>>, UrlencodedError> {
+        let mut map = HashMap::new();
+        for (key, value) in self.query_pairs()? {
+            map.entry(key).or_insert_with(Vec::new).push(value);
+        }
+        Ok(map)
+    }
+
+    /// Check that request target has no query string.
+    ///
+    /// Returns `ParseError::UnexpectedQuery` if query string is not empty.
+    pub fn expect_no_query(&self) -> Result<(), ParseError> {
+        if self.query_string().is_empty() {
+            Ok(())
+        } else {
+            Err(ParseError::UnexpectedQuery)
+        }
+    }
+
+    /// Return all decoded values of query parameter `name` in order of
+    /// appearance.
+    ///
+    /// Returns empty vec if parameter is absent.
+    pub fn query_values(&self, name: &str) -> Vec<String> {
+        form_urlencoded::parse(self.query_string().as_bytes())
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .collect()
+    }
+
+    /// Deserialize request's query string merged with urlencoded body.
+    ///
+    /// Body fields take precedence, query parameter is dropped if body
+    /// contains a field with the same name. If request has no content type,
+    /// only query string is used. Body size is limited to 256Kb.
+    pub fn params<T>(&mut self) -> Box<Future<Item = T, Error = UrlencodedError>>
+    where
+        P: Stream<Item = Bytes, Error = PayloadError> + 'static,
+        T: DeserializeOwned + 'static,
+    {
+        let query = match self.query_pairs() {
+            Ok(query) => query,
+            Err(e) => return Box::new(err(e)),
+        };
+        let merge = move |body: Vec<(String, String)>| {
+            let mut pairs: Vec<_> = query
+                .into_iter()
+                .filter(|(key, _)| !body.iter().any(|(name, _)| name == key))
+                .collect();
+            pairs.extend(body);
+            let params = serde_urlencoded::to_string(&pairs)
+                .map_err(|_| UrlencodedError::Parse)?;
+            serde_urlencoded::from_str::<T>(&params).map_err(|_| UrlencodedError::Parse)
+        };
+
+        if self.content_type().is_empty() {
+            Box::new(result(merge(Vec::new())))
+        } else {
+            Box::new(self.urlencoded::<Vec<(String, String)>>().and_then(merge))
+        }
+    }
+
+    fn query_pairs(&self) -> Result<Vec<(String, String)>, UrlencodedError> {
+        serde_urlencoded::from_str::<Vec<(String, String)>>(self.query_string())
+            .map_err(|_| UrlencodedError::Parse)
+    }
+
+    /// Compute canonical fingerprint of the request.
+    ///
+    /// Fingerprint is a *SHA-256* digest over method, path, selected headers
+    /// and body, hashed in the following order:
+    ///
+    /// * method and path, each followed by `\n`; query string is not included
+    /// * selected headers sorted by lowercase name, duplicates are ignored,
+    ///   each as `name:value\n`; multiple values are joined with `,` in order
+    ///   of appearance, missing header hashes as `name:\n`
+    /// * `\n` separator followed by raw body bytes
+    ///
+    /// Header values are hashed verbatim, body is hashed as it arrives and
+    /// is not buffered.
+    pub fn fingerprint(
+        &mut self,
+        include_headers: &[HeaderName],
+    ) -> Box<Future<Item = [u8; 32], Error = PayloadError>>
+    where
+        P: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    {
+        let mut names = include_headers.to_vec();
+        names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        names.dedup();
+
+        let mut hasher = Sha256::new();
+        hasher.input(self.method().as_str().as_bytes());
+        hasher.input(b"\n");
+        hasher.input(self.path().as_bytes());
+        hasher.input(b"\n");
+        for name in &names {
+            hasher.input(name.as_str().as_bytes());
+            hasher.input(b":");
+            for (idx, value) in self.headers().get_all(name).iter().enumerate() {
+                if idx > 0 {
+                    hasher.input(b",");
+                }
+                hasher.input(value.as_bytes());
+            }
+            hasher.input(b"\n");
+        }
+        hasher.input(b"\n");
+
+        Box::new(
+            self.take_payload()
+                .fold(hasher, |mut hasher, chunk| {
+                    hasher.input(&chunk);
+                    Ok::<_, PayloadError>(hasher)
+                })
+                .map(|hasher| {
+                    let mut digest = [0; 32];
+                    digest.copy_from_slice(&hasher.result());
+                    digest
+                }),
+        )
+    }
+
     /// Check if request requires connection upgrade
     pub fn upgrade(&self) -> bool {
         if let Some(conn) = self.head().headers.get(header::CONNECTION) {
@@ -167,3 +337,172 @@ impl<P> fmt::Debug for Request<P> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::Future;
+    use http::header::{HeaderName, HeaderValue};
+    use http::{header, Method, Version};
+    use serde_derive::Deserialize;
+
+    use super::*;
+    use crate::error::UrlencodedError;
+    use crate::test::TestRequest;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Info {
+        id: u32,
+    }
+
+    #[test]
+    fn test_query() {
+        let req = TestRequest::with_uri("/path?id=10").finish();
+        assert_eq!(req.path(), "/path");
+        assert_eq!(req.query_string(), "id=10");
+        assert_eq!(req.query::<Info>().unwrap(), Info { id: 10 });
+
+        let req = TestRequest::with_uri("/path").finish();
+        assert_eq!(req.query_string(), "");
+        assert!(req.query::<Info>().is_err());
+    }
+
+    #[test]
+    fn test_query_multi() {
+        let req = TestRequest::with_uri("/?a=1&b=x&a=2&a=3").finish();
+        let map = req.query_multi().unwrap();
+        assert_eq!(map["a"], vec!["1", "2", "3"]);
+        assert_eq!(map["b"], vec!["x"]);
+
+        let req = TestRequest::with_uri("/").finish();
+        assert!(req.query_multi().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_expect_no_query() {
+        assert!(TestRequest::with_uri("/path")
+            .finish()
+            .expect_no_query()
+            .is_ok());
+        assert!(TestRequest::with_uri("/path?")
+            .finish()
+            .expect_no_query()
+            .is_ok());
+        match TestRequest::with_uri("/path?token=secret")
+            .finish()
+            .expect_no_query()
+        {
+            Err(ParseError::UnexpectedQuery) => (),
+            _ => unreachable!("error"),
+        }
+    }
+
+    #[test]
+    fn test_query_values() {
+        let req = TestRequest::with_uri("/?tag=a&x=1&tag=b%20c&tag=").finish();
+        assert_eq!(req.query_values("tag"), vec!["a", "b c", ""]);
+        assert!(req.query_values("missing").is_empty());
+    }
+
+    #[test]
+    fn test_params() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Params {
+            id: u32,
+            name: String,
+        }
+
+        let mut req = TestRequest::with_uri("/path?id=10&name=query")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .set_payload(Bytes::from_static(b"name=body"))
+            .finish();
+        assert_eq!(
+            req.params::<Params>().wait().unwrap(),
+            Params {
+                id: 10,
+                name: "body".to_owned()
+            }
+        );
+
+        let mut req = TestRequest::with_uri("/path?id=10&name=query").finish();
+        assert_eq!(
+            req.params::<Params>().wait().unwrap(),
+            Params {
+                id: 10,
+                name: "query".to_owned()
+            }
+        );
+
+        let mut req = TestRequest::with_uri("/path?id=10")
+            .header(header::CONTENT_TYPE, "text/plain")
+            .set_payload(Bytes::from_static(b"name=body"))
+            .finish();
+        assert_eq!(
+            req.params::<Params>().wait().err().unwrap(),
+            UrlencodedError::ContentType
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let names = [header::CONTENT_TYPE, HeaderName::from_static("x-tenant")];
+        let mut req = TestRequest::with_uri("/path?a=1")
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .header("x-tenant", "acme")
+            .header("x-trace", "1")
+            .set_payload(Bytes::from_static(b"body"))
+            .finish();
+        let first = req.fingerprint(&names).wait().unwrap();
+
+        let stream: PayloadStream =
+            Box::new(futures::stream::iter_ok::<_, PayloadError>(vec![
+                Bytes::from_static(b"bo"),
+                Bytes::from_static(b"dy"),
+            ]));
+        let mut req = Request::with_payload(Payload::Stream(stream));
+        req.head_mut().method = Method::POST;
+        req.head_mut().uri = "/path?a=2".parse().unwrap();
+        req.head_mut()
+            .headers
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        req.head_mut()
+            .headers
+            .insert("x-tenant", HeaderValue::from_static("acme"));
+        let names = [HeaderName::from_static("x-tenant"), header::CONTENT_TYPE];
+        assert_eq!(req.fingerprint(&names).wait().unwrap(), first);
+
+        let mut req = TestRequest::with_uri("/path")
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .header("x-tenant", "other")
+            .set_payload(Bytes::from_static(b"body"))
+            .finish();
+        assert_ne!(req.fingerprint(&names).wait().unwrap(), first);
+    }
+
+    #[test]
+    fn test_is_retry_safe() {
+        let req = TestRequest::default().finish();
+        assert!(req.is_retry_safe());
+
+        let req = TestRequest::default().method(Method::POST).finish();
+        assert!(!req.is_retry_safe());
+
+        let req = TestRequest::with_header("idempotency-key", "8e03978e")
+            .method(Method::POST)
+            .finish();
+        assert!(req.is_retry_safe());
+
+        let req = TestRequest::with_header("idempotency-key", "  ")
+            .method(Method::POST)
+            .finish();
+        assert!(!req.is_retry_safe());
+    }
+
+    #[test]
+    fn test_http_version() {
+        let req = TestRequest::default().version(Version::HTTP_10).finish();
+        assert_eq!(req.http_version(), Some(Version::HTTP_10));
+    }
+}